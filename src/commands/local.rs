@@ -1,16 +1,23 @@
 use crate::commands::group_dispatch::GroupDispatch;
-use crate::fingerprinting::algorithm::SignatureGenerator;
 use crate::music::bests::Bests;
 use crate::music::clean::Clean;
 use crate::music::config::Config;
+use crate::music::duplicates::Duplicates;
 use crate::music::errors::CriticalErrorKind;
+use crate::music::fingerprints::Fingerprint;
 use crate::music::folders::Folders;
-use crate::music::playlist::{OutputOptions, PlaylistCommand};
+use crate::music::lossless::Lossless;
+use crate::music::loudness::Loudness;
+use crate::music::play::Play;
+use crate::music::playlist::{OutputOptions, PlaylistAction, PlaylistCommand};
+use crate::music::recognize::Recognize;
 use crate::music::remove::Remove;
 use crate::music::scan::Scan;
 use crate::music::search::Search;
-use crate::music::shazam::{Shazam, try_recognize_song};
+use crate::music::shazam::Shazam;
 use crate::music::stats::Stats;
+use crate::music::verify::Verify;
+use crate::music::verify_tags::VerifyTags;
 use async_trait::async_trait;
 
 #[derive(clap::Subcommand)]
@@ -23,6 +30,8 @@ pub enum Group {
     Stats(Stats),
     #[clap(about = "Generate a new playlist")]
     Playlist(PlaylistCommand),
+    #[clap(about = "Play musics")]
+    Play(Play),
     #[clap(about = "Search musics")]
     Search(Search),
     #[clap(about = "Manage folders")]
@@ -33,6 +42,20 @@ pub enum Group {
     Bests(Bests),
     #[clap(about = "Detect song")]
     Shazam(Shazam),
+    #[clap(about = "Recognize musics with Shazam and store results")]
+    Recognize(Recognize),
+    #[clap(about = "Compare tags with Shazam recognition")]
+    VerifyTags(VerifyTags),
+    #[clap(about = "Verify audio integrity, decoding musics fully")]
+    Verify(Verify),
+    #[clap(about = "Detect lossless musics transcoded from lossy or upsampled files")]
+    Lossless(Lossless),
+    #[clap(about = "Measure EBU R128 loudness of tracks and albums")]
+    Loudness(Loudness),
+    #[clap(about = "Index audio fingerprints of musics, for offline matching")]
+    Fingerprint(Fingerprint),
+    #[clap(about = "Find duplicated musics")]
+    Duplicates(Duplicates),
 }
 
 #[async_trait]
@@ -43,6 +66,14 @@ impl GroupDispatch for Group {
             Group::Clean(clean_cmd) => clean_cmd.clean(config.gel, config.dry).await,
             Group::Playlist(playlist_cmd) => {
                 let playlist = playlist_cmd.playlist(config.gel).await?;
+                if let Some(PlaylistAction::Export(export_cmd)) = playlist_cmd.action() {
+                    return export_cmd.export(
+                        &playlist,
+                        playlist_cmd.playlist_options(),
+                        config.dry,
+                        config.ffmpeg.as_deref(),
+                    );
+                }
                 playlist.generate(
                     playlist_cmd.output_options(),
                     playlist_cmd.playlist_options(),
@@ -65,6 +96,7 @@ impl GroupDispatch for Group {
                 }
                 Ok(())
             }
+            Group::Play(play_cmd) => Box::pin(play_cmd.play(config)).await,
             Group::Search(search_cmd) => {
                 let playlist = search_cmd.search(config.gel).await?;
                 playlist.generate(
@@ -108,21 +140,18 @@ impl GroupDispatch for Group {
                 }
                 Ok(())
             }
-            Group::Shazam(shazam_cmd) => {
-                let song = try_recognize_song(
-                    shazam_cmd.file.clone(),
-                    &SignatureGenerator::make_signature_from_file(&shazam_cmd.file)?,
-                )
-                .await?;
-                println!("Artist : {}", song.artist_name);
-                println!(
-                    "Album : {}",
-                    song.album_name.unwrap_or("Unknown".to_string())
-                );
-                println!("Song : {}", song.song_name);
-                println!("Path : {}", song.path);
-                Ok(())
+            Group::Shazam(shazam_cmd) => Box::pin(shazam_cmd.shazam(&config)).await,
+            Group::Recognize(recognize_cmd) => Box::pin(recognize_cmd.recognize(config)).await,
+            Group::VerifyTags(verify_tags_cmd) => {
+                Box::pin(verify_tags_cmd.verify_tags(config)).await
+            }
+            Group::Verify(verify_cmd) => Box::pin(verify_cmd.verify(config)).await,
+            Group::Lossless(lossless_cmd) => Box::pin(lossless_cmd.lossless(config)).await,
+            Group::Loudness(loudness_cmd) => Box::pin(loudness_cmd.loudness(config)).await,
+            Group::Fingerprint(fingerprint_cmd) => {
+                Box::pin(fingerprint_cmd.fingerprint(config)).await
             }
+            Group::Duplicates(duplicates_cmd) => duplicates_cmd.duplicates(&config),
         }
     }
 }
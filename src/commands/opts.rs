@@ -1,6 +1,6 @@
 use crate::commands::group_dispatch::GroupDispatch;
 use crate::commands::root::Root;
-use crate::commands::{DEFAULT_DATASTORE_FILE, DEFAULT_DSN};
+use crate::commands::{DEFAULT_DATASTORE_FILE, DEFAULT_DSN, DEFAULT_FINGERPRINTS_FILE};
 use crate::music::config::Config;
 use crate::music::errors::CriticalErrorKind;
 use clap::Parser;
@@ -41,6 +41,19 @@ pub struct Opts {
     /// Datastore path
     pub datastore: String,
 
+    #[clap(
+        long,
+        global = true,
+        default_value_t = datastore_file_path(DEFAULT_FINGERPRINTS_FILE)
+    )]
+    /// Audio fingerprints index path
+    pub fingerprints: String,
+
+    #[cfg(feature = "ffmpeg")]
+    #[clap(long, global = true)]
+    /// FFMpeg path, searched on the system by default
+    pub ffmpeg: Option<String>,
+
     #[clap(long, global = true)]
     /// Disable Gel DB
     pub no_gel: bool,
@@ -52,7 +65,19 @@ pub struct Opts {
 
 impl Opts {
     pub async fn dispatch(self) -> Result<(), CriticalErrorKind> {
-        let config = Config::new(self.dsn, self.dry, self.no_gel)?;
+        let mut config = Config::new(self.dsn, self.dry, self.no_gel)?;
+        config.datastore = self.datastore;
+        config.fingerprints = self.fingerprints;
+        #[cfg(feature = "ffmpeg")]
+        {
+            config.ffmpeg = self.ffmpeg;
+        }
         self.root.dispatch(config).await
     }
 }
+
+#[test]
+fn opts_tests() {
+    use clap::CommandFactory;
+    Opts::command().debug_assert();
+}
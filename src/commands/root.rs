@@ -1,5 +1,8 @@
 use crate::commands::group_dispatch::GroupDispatch;
 use crate::commands::local::Group;
+use crate::commands::serve::Serve;
+use crate::commands::sig::Sig;
+use crate::commands::tui::Tui;
 use crate::music::config::Config;
 use crate::music::errors::CriticalErrorKind;
 
@@ -8,7 +11,12 @@ use enum_dispatch::enum_dispatch;
 
 #[enum_dispatch(GroupDispatch)]
 #[derive(Parser)]
+#[allow(clippy::large_enum_variant)]
 pub enum Root {
     #[clap(subcommand)]
     Local(Group),
+    Serve(Serve),
+    Tui(Tui),
+    #[clap(subcommand)]
+    Sig(Sig),
 }
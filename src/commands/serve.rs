@@ -0,0 +1,88 @@
+use crate::commands::group_dispatch::GroupDispatch;
+use crate::music::config::Config;
+use crate::music::errors::CriticalErrorKind;
+use crate::server::auth::Credentials;
+use crate::server::{ServerState, api, files, subsonic};
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+const DEFAULT_BIND: &str = "127.0.0.1:8080";
+
+#[derive(clap::Parser)]
+#[clap(about = "Serve music files of this host over HTTP")]
+pub struct Serve {
+    /// Listening address, files are readable by anyone who can reach it unless a user is set
+    #[clap(long, default_value = DEFAULT_BIND)]
+    bind: SocketAddr,
+
+    /// Expose the JSON API, described by /openapi.json, changing the library only when a user is set
+    #[clap(long)]
+    api: bool,
+
+    /// User required to access the server
+    #[clap(long, env = "CRITICAL_USER", requires = "password_source")]
+    user: Option<String>,
+
+    /// Password of the user, visible in the process list, prefer the environment variable or a file
+    #[clap(
+        long,
+        env = "CRITICAL_PASSWORD",
+        hide_env_values = true,
+        group = "password_source",
+        requires = "user"
+    )]
+    password: Option<String>,
+
+    /// File holding the password of the user on its first line
+    #[clap(long, group = "password_source", requires = "user")]
+    password_file: Option<PathBuf>,
+
+    /// Expose the Subsonic API under /rest, clients log in as the user
+    #[clap(long, requires = "user")]
+    subsonic: bool,
+}
+
+impl Serve {
+    fn credentials(&self) -> Result<Option<Credentials>, CriticalErrorKind> {
+        let Some(user) = &self.user else {
+            return Ok(None);
+        };
+        let password = match (&self.password, &self.password_file) {
+            (Some(password), _) => password.clone(),
+            (None, Some(password_file)) => std::fs::read_to_string(password_file)?
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            (None, None) => return Ok(None),
+        };
+        Ok(Some(Credentials::new(user, &password)))
+    }
+}
+
+#[async_trait]
+impl GroupDispatch for Serve {
+    async fn dispatch(self, config: Config) -> Result<(), CriticalErrorKind> {
+        let credentials = self.credentials()?;
+        let state = ServerState::new(config.gel.clone()).await?;
+        for folder in state.folders() {
+            eprintln!("Serving {}", folder.display());
+        }
+        let listener = tokio::net::TcpListener::bind(self.bind).await?;
+        eprintln!("Listening on http://{}", self.bind);
+        let mut router = files::router(state.clone(), credentials.clone());
+        if self.api {
+            eprintln!("JSON API on http://{}/openapi.json", self.bind);
+            router = router.merge(api::router(config, credentials.clone()));
+        }
+        if self.subsonic
+            && let Some(credentials) = credentials
+        {
+            eprintln!("Subsonic API on http://{}/rest", self.bind);
+            router = router.merge(subsonic::router(state, credentials));
+        }
+        axum::serve(listener, router).await?;
+        Ok(())
+    }
+}
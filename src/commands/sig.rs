@@ -0,0 +1,253 @@
+use crate::commands::group_dispatch::GroupDispatch;
+use crate::fingerprinting::algorithm::SignatureGenerator;
+use crate::fingerprinting::signature_format::DecodedSignature;
+use crate::music::config::Config;
+use crate::music::errors::CriticalErrorKind;
+use crate::music::helpers::{ReportOutput, parse_duration};
+use crate::music::recognize::RecognizerOptions;
+use crate::music::shazam::parse_song;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::time::Duration;
+use tabled::{Table, Tabled};
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq, Debug)]
+pub enum SignatureEncoding {
+    /// Raw signature, as sent by Shazam clients
+    #[default]
+    Binary,
+    /// Base64 data URI, printable and easy to paste
+    Uri,
+}
+
+impl SignatureEncoding {
+    fn encode(self, signature: &DecodedSignature) -> Result<Vec<u8>, CriticalErrorKind> {
+        Ok(match self {
+            SignatureEncoding::Binary => signature.encode_to_binary()?,
+            SignatureEncoding::Uri => signature.encode_to_uri()?.into_bytes(),
+        })
+    }
+}
+
+#[derive(clap::Subcommand)]
+#[clap(about = "Shazam signature files")]
+pub enum Sig {
+    Make(SigMake),
+    Inspect(SigInspect),
+    Recognize(SigRecognize),
+    Convert(SigConvert),
+}
+
+#[derive(clap::Parser)]
+#[clap(about = "Make a signature from an audio file")]
+pub struct SigMake {
+    /// Audio file
+    file: String,
+
+    /// Signature file, the data URI being printed when not given
+    #[clap(short, long)]
+    out: Option<String>,
+
+    /// Signature file encoding
+    #[clap(long, value_enum, default_value_t)]
+    encoding: SignatureEncoding,
+
+    /// Start of the window, the middle of the file by default
+    #[clap(long, value_parser = parse_duration)]
+    offset: Option<Duration>,
+
+    /// Duration of the window
+    #[clap(long, default_value = "12s", value_parser = parse_duration)]
+    duration: Duration,
+}
+
+#[derive(clap::Parser)]
+#[clap(about = "Show sample rate, length and peaks of a signature")]
+pub struct SigInspect {
+    /// Signature file, binary or data URI
+    file: String,
+
+    /// Report format
+    #[clap(long, value_enum, default_value_t)]
+    output: ReportOutput,
+}
+
+#[derive(clap::Parser)]
+#[clap(about = "Recognize a signature with Shazam")]
+pub struct SigRecognize {
+    /// Signature file, binary or data URI
+    file: String,
+
+    /// Recognition options
+    #[clap(flatten)]
+    recognizer_options: RecognizerOptions,
+}
+
+#[derive(clap::Parser)]
+#[clap(about = "Convert a signature between binary and data URI")]
+pub struct SigConvert {
+    /// Signature file, binary or data URI
+    file: String,
+
+    /// Converted signature file
+    out: String,
+
+    /// Target encoding, the other one than the input by default
+    #[clap(long, value_enum)]
+    to: Option<SignatureEncoding>,
+}
+
+#[derive(Serialize, Tabled)]
+pub struct BandPeaks {
+    pub band: String,
+    pub peaks: usize,
+}
+
+#[derive(Serialize)]
+pub struct SignatureInfo {
+    pub sample_rate_hz: u32,
+    pub number_samples: u32,
+    pub duration: f64,
+    pub bands: Vec<BandPeaks>,
+}
+
+impl SignatureInfo {
+    #[must_use]
+    pub fn new(signature: &DecodedSignature) -> Self {
+        let mut bands = signature
+            .frequency_band_to_sound_peaks
+            .iter()
+            .collect::<Vec<_>>();
+        bands.sort_by_key(|(band, _)| **band);
+        Self {
+            sample_rate_hz: signature.sample_rate_hz,
+            number_samples: signature.number_samples,
+            duration: f64::from(signature.number_samples) / f64::from(signature.sample_rate_hz),
+            bands: bands
+                .into_iter()
+                .map(|(band, peaks)| BandPeaks {
+                    band: band.to_string(),
+                    peaks: peaks.len(),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn read_signature(path: &str) -> Result<(DecodedSignature, SignatureEncoding), CriticalErrorKind> {
+    let data = std::fs::read(path)?;
+    let encoding = if DecodedSignature::is_uri(&data) {
+        SignatureEncoding::Uri
+    } else {
+        SignatureEncoding::Binary
+    };
+    Ok((DecodedSignature::decode_from_data(&data)?, encoding))
+}
+
+fn write_signature(
+    path: &str,
+    signature: &DecodedSignature,
+    encoding: SignatureEncoding,
+    dry: bool,
+) -> Result<(), CriticalErrorKind> {
+    let data = encoding.encode(signature)?;
+    if dry {
+        eprintln!("Write signature {path}");
+        return Ok(());
+    }
+    Ok(std::fs::write(path, data)?)
+}
+
+#[async_trait]
+impl GroupDispatch for Sig {
+    async fn dispatch(self, config: Config) -> Result<(), CriticalErrorKind> {
+        match self {
+            Sig::Make(make_cmd) => {
+                let signature = SignatureGenerator::make_signature_from_window(
+                    &make_cmd.file,
+                    config.ffmpeg.as_deref(),
+                    make_cmd.offset,
+                    make_cmd.duration,
+                )?;
+                if let Some(out) = make_cmd.out {
+                    return write_signature(&out, &signature, make_cmd.encoding, config.dry);
+                }
+                println!("{}", signature.encode_to_uri()?);
+                Ok(())
+            }
+            Sig::Inspect(inspect_cmd) => {
+                let (signature, _) = read_signature(&inspect_cmd.file)?;
+                let info = SignatureInfo::new(&signature);
+                match inspect_cmd.output {
+                    ReportOutput::Table => {
+                        println!("Sample rate : {} Hz", info.sample_rate_hz);
+                        println!("Samples : {}", info.number_samples);
+                        println!("Duration : {:.2}s", info.duration);
+                        println!("{}", Table::new(&info.bands));
+                    }
+                    ReportOutput::Json => println!("{}", serde_json::to_string_pretty(&info)?),
+                }
+                Ok(())
+            }
+            Sig::Recognize(recognize_cmd) => {
+                let (signature, _) = read_signature(&recognize_cmd.file)?;
+                let response = recognize_cmd
+                    .recognizer_options
+                    .recognizer()?
+                    .recognize(&signature)
+                    .await?;
+                println!("{}", parse_song(recognize_cmd.file, &response)?);
+                Ok(())
+            }
+            Sig::Convert(convert_cmd) => {
+                let (signature, encoding) = read_signature(&convert_cmd.file)?;
+                let to = convert_cmd.to.unwrap_or(match encoding {
+                    SignatureEncoding::Binary => SignatureEncoding::Uri,
+                    SignatureEncoding::Uri => SignatureEncoding::Binary,
+                });
+                write_signature(&convert_cmd.out, &signature, to, config.dry)
+            }
+        }
+    }
+}
+
+#[test]
+#[allow(clippy::cast_possible_truncation)]
+fn signature_files_tests() {
+    // Tone rising from 400 Hz, giving peaks over the frequency bands
+    let samples = (0..16000 * 3)
+        .map(|n| {
+            let t = f64::from(n) / 16000.0;
+            ((2.0 * std::f64::consts::PI * (400.0 + 600.0 * t) * t).sin() * 8000.0) as i16
+        })
+        .collect::<Vec<i16>>();
+    let signature = SignatureGenerator::make_signature_from_buffer(&samples);
+    let dir = tempfile::tempdir().unwrap();
+    let binary = dir.path().join("a.sig").display().to_string();
+    let uri = dir.path().join("a.txt").display().to_string();
+
+    write_signature(&binary, &signature, SignatureEncoding::Binary, false).unwrap();
+    let (read, encoding) = read_signature(&binary).unwrap();
+    assert_eq!(encoding, SignatureEncoding::Binary);
+    write_signature(&uri, &read, SignatureEncoding::Uri, false).unwrap();
+    let (read, encoding) = read_signature(&uri).unwrap();
+    assert_eq!(encoding, SignatureEncoding::Uri);
+    assert_eq!(
+        read.encode_to_binary().unwrap(),
+        signature.encode_to_binary().unwrap()
+    );
+
+    let info = SignatureInfo::new(&read);
+    assert_eq!(info.sample_rate_hz, 16000);
+    assert_eq!(info.number_samples, 48000);
+    assert!((info.duration - 3.0).abs() < f64::EPSILON);
+    assert_eq!(
+        info.bands.iter().map(|band| band.peaks).sum::<usize>(),
+        signature
+            .frequency_band_to_sound_peaks
+            .values()
+            .map(Vec::len)
+            .sum::<usize>()
+    );
+    assert!(read_signature(&dir.path().join("missing.sig").display().to_string()).is_err());
+}
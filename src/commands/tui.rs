@@ -0,0 +1,178 @@
+use crate::commands::group_dispatch::GroupDispatch;
+use crate::music::cache::UpsertCache;
+use crate::music::config::Config;
+use crate::music::errors::CriticalErrorKind;
+use crate::music::filter::{Filters, validate_filters};
+use crate::music::keywords::Keyword;
+use crate::music::music::{UPDATE_KEYWORDS_QUERY, UPDATE_RATING_QUERY};
+use crate::music::music_file::open_music_file;
+use crate::music::music_result::MusicResult;
+use crate::music::playlist::{OutputOptions, PLAYLIST_QUERY, Playlist, PlaylistOptions};
+use crate::music::ratings::Rating;
+use crate::music::vertex::Vertex;
+use crate::tui::{Action, App, ui};
+use async_trait::async_trait;
+use crossterm::event::{self, Event, KeyEventKind};
+use std::time::Duration;
+
+const DEFAULT_NAME: &str = "default";
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const RETRIES: u16 = 3;
+
+#[derive(clap::Parser)]
+#[clap(about = "Browse and curate the library in a terminal interface")]
+pub struct Tui {
+    /// Playlist name
+    #[clap(long, default_value_t = DEFAULT_NAME.to_string())]
+    name: String,
+
+    /// Playlist options
+    #[clap(flatten)]
+    playlist_options: PlaylistOptions,
+
+    /// Output options
+    #[clap(flatten)]
+    output_options: OutputOptions,
+
+    /// Initial filters
+    #[clap(flatten)]
+    filters: Filters,
+}
+
+#[async_trait]
+impl GroupDispatch for Tui {
+    async fn dispatch(self, mut config: Config) -> Result<(), CriticalErrorKind> {
+        config.retries = RETRIES;
+        let playlist = Box::pin(Playlist::from_filters(
+            &config.gel,
+            &self.name,
+            &self.filters,
+        ))
+        .await?;
+        let mut app = App::new(playlist.ordered_musics(&PlaylistOptions::default())?);
+
+        let write = {
+            let mut terminal = ratatui::init();
+            scopeguard::defer! {
+                ratatui::restore();
+            };
+            loop {
+                terminal
+                    .draw(|frame| ui::draw(frame, &app, self.playlist_options.link_options()))?;
+                if !event::poll(POLL_INTERVAL)? {
+                    continue;
+                }
+                let Event::Key(key) = event::read()? else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                let result = match app.handle_key(key) {
+                    Action::None => Ok(()),
+                    Action::Quit => break false,
+                    Action::Write => break true,
+                    Action::ApplyFilter(filter) => {
+                        Box::pin(apply_filter(&config, &mut app, &filter)).await
+                    }
+                    Action::Rate(music, rating) => {
+                        Box::pin(rate(&config, &mut app, &music, rating)).await
+                    }
+                    Action::SetKeywords(music, keywords) => {
+                        Box::pin(set_keywords(&config, &mut app, &music, &keywords)).await
+                    }
+                };
+                if let Err(e) = result {
+                    app.set_status(e.to_string());
+                }
+            }
+        };
+
+        if write {
+            Playlist::new(&self.name, app.playlist()).generate(
+                &self.output_options,
+                &self.playlist_options,
+                config.dry,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+async fn apply_filter(
+    config: &Config,
+    app: &mut App,
+    filter: &str,
+) -> Result<(), CriticalErrorKind> {
+    let filter = if filter.is_empty() {
+        crate::music::filter::Filter::default()
+    } else {
+        validate_filters(filter).map_err(CriticalErrorKind::InvalidFilter)?
+    };
+    let music_filter = serde_json::to_string(&filter)?;
+    let musics: Vec<MusicResult> =
+        Box::pin(config.gel.query(PLAYLIST_QUERY, &(music_filter,))).await?;
+    app.set_status(format!("{} musics", musics.len()));
+    app.set_musics(musics);
+    Ok(())
+}
+
+async fn rate(
+    config: &Config,
+    app: &mut App,
+    music: &MusicResult,
+    rating: Rating,
+) -> Result<(), CriticalErrorKind> {
+    if !config.dry {
+        for folder in music.local_folders() {
+            let mut music_file = open_music_file(&folder.name, &folder.path)?;
+            music_file.set_rating(rating);
+            music_file.save()?;
+        }
+        for folder in &music.folders {
+            Box::pin(
+                config
+                    .gel
+                    .execute(UPDATE_RATING_QUERY, &(&folder.path, f64::from(rating))),
+            )
+            .await?;
+        }
+    }
+    app.update_music(music, |m| m.rating = rating.into());
+    app.set_status(format!("{} rated {rating}", music.name));
+    Ok(())
+}
+
+async fn set_keywords(
+    config: &Config,
+    app: &mut App,
+    music: &MusicResult,
+    keywords: &[String],
+) -> Result<(), CriticalErrorKind> {
+    if !config.dry {
+        for folder in music.local_folders() {
+            let mut music_file = open_music_file(&folder.name, &folder.path)?;
+            music_file.set_keywords(keywords);
+            music_file.save()?;
+        }
+        let mut cache = UpsertCache::default();
+        let mut keywords_gel = Vec::new();
+        for keyword in keywords {
+            let keyword = Keyword {
+                name: keyword.clone(),
+            };
+            keywords_gel.push(keyword.upsert_gel(config, &mut cache).await?);
+        }
+        for folder in &music.folders {
+            Box::pin(
+                config
+                    .gel
+                    .execute(UPDATE_KEYWORDS_QUERY, &(&folder.path, keywords_gel.clone())),
+            )
+            .await?;
+        }
+    }
+    app.update_music(music, |m| m.keywords_names = keywords.to_vec());
+    app.set_status(format!("{} keywords: {}", music.name, keywords.join(" ")));
+    Ok(())
+}
@@ -1,11 +1,13 @@
 #[cfg(feature = "ffmpeg")]
-use crate::fingerprinting::ffmpeg_wrapper::decode_with_ffmpeg;
+use crate::fingerprinting::ffmpeg_wrapper::{find_ffmpeg, stream_with_ffmpeg};
 use crate::fingerprinting::hanning::HANNING_WINDOW_2048_MULTIPLIERS;
 use crate::fingerprinting::signature_format::{DecodedSignature, FrequencyBand, FrequencyPeak};
 use crate::music::errors::CriticalErrorKind;
 use chfft::RFft1D;
+use rodio::Source;
 use std::collections::HashMap;
 use std::io::BufReader;
+use std::time::Duration;
 
 pub struct SignatureGenerator {
     // Used when processing input:
@@ -27,96 +29,370 @@ pub struct SignatureGenerator {
 
     num_spread_ffts_done: u32,
 
+    /// Samples waiting for a full chunk of 128 to be processed
+    pending_samples: Vec<i16>,
+    /// FFT pass at which the current signature started, in rolling mode
+    signature_start_pass: u32,
+
     signature: DecodedSignature,
 }
 
+impl Default for SignatureGenerator {
+    fn default() -> Self {
+        SignatureGenerator {
+            ring_buffer_of_samples: vec![0i16; 2048],
+            ring_buffer_of_samples_index: 0,
+
+            reordered_ring_buffer_of_samples: vec![0.0f32; 2048],
+
+            fft_outputs: vec![vec![0.0f32; 1025]; 256],
+            fft_outputs_index: 0,
+
+            fft_object: RFft1D::<f32>::new(2048),
+
+            spread_fft_outputs: vec![vec![0.0f32; 1025]; 256],
+            spread_fft_outputs_index: 0,
+
+            num_spread_ffts_done: 0,
+
+            pending_samples: Vec::with_capacity(128),
+            signature_start_pass: 0,
+
+            signature: DecodedSignature {
+                sample_rate_hz: 16000,
+                number_samples: 0,
+                frequency_band_to_sound_peaks: HashMap::new(),
+            },
+        }
+    }
+}
+
+/// Sample rate of the PCM signatures are computed from
+pub const SAMPLE_RATE: usize = 16000;
+/// Duration of the sample taken for a signature, in seconds
+pub const SIGNATURE_SECONDS: usize = 12;
+/// Samples given at once while decoding
+const STREAM_CHUNK: usize = 4096;
+
+/// Number of samples lasting a duration at the signature sample rate
+#[must_use]
+pub fn samples_of(duration: Duration) -> usize {
+    usize::try_from(duration.as_millis() * SAMPLE_RATE as u128 / 1000).unwrap_or(usize::MAX)
+}
+
+/// Duration of a number of samples at the signature sample rate
+#[must_use]
+pub fn duration_of(samples: usize) -> Duration {
+    Duration::from_millis((samples * 1000 / SAMPLE_RATE) as u64)
+}
+
+/// Samples of a window centered in the buffer, or the whole buffer when shorter
+fn middle(samples: &[i16], window: usize) -> &[i16] {
+    if samples.len() > window {
+        let middle = samples.len() / 2;
+        &samples[middle - (window / 2)..middle + (window / 2)]
+    } else {
+        samples
+    }
+}
+
 impl SignatureGenerator {
-    pub fn make_signature_from_file(
+    fn open_decoder(
         file_path: &str,
-    ) -> Result<DecodedSignature, CriticalErrorKind> {
+    ) -> Result<rodio::Decoder<BufReader<std::fs::File>>, CriticalErrorKind> {
         // Decode the .WAV, .MP3, .OGG or .FLAC file
 
-        #[cfg(not(feature = "ffmpeg"))]
-        let decoder = rodio::Decoder::new(BufReader::new(std::fs::File::open(file_path)?));
+        Ok(rodio::Decoder::new(BufReader::new(std::fs::File::open(
+            file_path,
+        )?))?)
+    }
 
-        #[cfg(feature = "ffmpeg")]
-        let decoder = {
-            let mut decoder = rodio::Decoder::new(BufReader::new(std::fs::File::open(file_path)?));
+    /// Try to decode with FFMpeg, if available, in case of failure with
+    /// Rodio (most likely due to the use of a format unsupported by
+    /// Rodio, such as .WMA or .MP4/.AAC)
+    #[cfg(feature = "ffmpeg")]
+    fn stream_unsupported(
+        file_path: &str,
+        ffmpeg: Option<&str>,
+        window: Option<(Duration, Duration)>,
+        decoding_error: CriticalErrorKind,
+        on_samples: &mut dyn FnMut(&[i16]),
+    ) -> Result<(), CriticalErrorKind> {
+        let Some(ffmpeg_path) = find_ffmpeg(ffmpeg) else {
+            // A configured FFMpeg is expected to be there
+            return Err(match ffmpeg {
+                Some(_) => CriticalErrorKind::FfmpegNotFound,
+                None => decoding_error,
+            });
+        };
+        stream_with_ffmpeg(&ffmpeg_path, file_path, window, on_samples)
+    }
 
-            if let Err(ref _decoding_error) = decoder {
-                // Try to decode with FFMpeg, if available, in case of failure with
-                // Rodio (most likely due to the use of a format unsupported by
-                // Rodio, such as .WMA or .MP4/.AAC)
+    #[cfg(not(feature = "ffmpeg"))]
+    fn stream_unsupported(
+        _file_path: &str,
+        _ffmpeg: Option<&str>,
+        _window: Option<(Duration, Duration)>,
+        decoding_error: CriticalErrorKind,
+        _on_samples: &mut dyn FnMut(&[i16]),
+    ) -> Result<(), CriticalErrorKind> {
+        Err(decoding_error)
+    }
 
-                if let Some(new_decoder) = decode_with_ffmpeg(file_path) {
-                    decoder = Ok(new_decoder);
-                }
-            }
+    fn decode_unsupported(
+        file_path: &str,
+        ffmpeg: Option<&str>,
+        window: Option<(Duration, Duration)>,
+        decoding_error: CriticalErrorKind,
+    ) -> Result<Vec<i16>, CriticalErrorKind> {
+        let mut samples = Vec::new();
+        Self::stream_unsupported(file_path, ffmpeg, window, decoding_error, &mut |chunk| {
+            samples.extend_from_slice(chunk);
+        })?;
+        Ok(samples)
+    }
 
-            decoder
+    /// Duration of a file given by its container, when Rodio can decode it
+    #[must_use]
+    pub fn file_duration(file_path: &str) -> Option<Duration> {
+        Self::open_decoder(file_path).ok()?.total_duration()
+    }
+
+    /// Decode a file to mono 16 KHz PCM samples, with FFMpeg at the given path when Rodio can't
+    pub fn decode_file(
+        file_path: &str,
+        ffmpeg: Option<&str>,
+    ) -> Result<Vec<i16>, CriticalErrorKind> {
+        let decoder = match Self::open_decoder(file_path) {
+            Ok(decoder) => decoder,
+            Err(e @ CriticalErrorKind::DecoderError(_)) => {
+                return Self::decode_unsupported(file_path, ffmpeg, None, e);
+            }
+            Err(e) => return Err(e),
         };
 
-        // Downsample the raw PCM samples to 16 KHz, and skip to the middle of the file
-        // in order to increase recognition odds. Take 12 seconds of sample.
+        // Downsample the raw PCM samples to 16 KHz
 
-        let converted_file = rodio::source::UniformSourceIterator::new(decoder?, 1, 16000);
+        #[allow(clippy::cast_possible_truncation)]
+        let converted_file =
+            rodio::source::UniformSourceIterator::new(decoder, 1, SAMPLE_RATE as u32);
 
-        let raw_pcm_samples: Vec<i16> = converted_file.collect();
-        let mut raw_pcm_samples_slice: &[i16] = &raw_pcm_samples;
+        Ok(converted_file.collect())
+    }
+
+    /// Decode a window of a file to mono 16 KHz PCM samples, the middle one when no offset is
+    /// given, giving the same samples as slicing the whole decoded file.
+    ///
+    /// Sample based decoders, like WAV or FLAC ones, skip input frames before the window without
+    /// resampling them. Frame based decoders, like MP3 ones, can not seek to an exact sample nor
+    /// be resampled from the middle of a frame: they are decoded from the start, their samples
+    /// before the window being dropped as they come, and the rest of the file is not decoded.
+    pub fn decode_file_window(
+        file_path: &str,
+        ffmpeg: Option<&str>,
+        offset: Option<Duration>,
+        duration: Duration,
+    ) -> Result<Vec<i16>, CriticalErrorKind> {
+        let mut samples = Vec::new();
+        Self::stream_file_window(file_path, ffmpeg, offset, duration, &mut |chunk| {
+            samples.extend_from_slice(chunk);
+        })?;
+        Ok(samples)
+    }
 
-        let slice_len = raw_pcm_samples_slice.len().min(12 * 16000);
+    /// Decode a window of a file like `decode_file_window`, giving samples to a callback in
+    /// chunks while decoding
+    fn stream_file_window(
+        file_path: &str,
+        ffmpeg: Option<&str>,
+        offset: Option<Duration>,
+        duration: Duration,
+        on_samples: &mut dyn FnMut(&[i16]),
+    ) -> Result<(), CriticalErrorKind> {
+        let window = samples_of(duration);
+        let mut decoder = match Self::open_decoder(file_path) {
+            Ok(decoder) => decoder,
+            // FFMpeg seeks by itself, the middle being only known once decoded
+            Err(e @ CriticalErrorKind::DecoderError(_)) => {
+                if let Some(offset) = offset {
+                    return Self::stream_unsupported(
+                        file_path,
+                        ffmpeg,
+                        Some((offset, duration)),
+                        e,
+                        on_samples,
+                    );
+                }
+                on_samples(middle(
+                    &Self::decode_unsupported(file_path, ffmpeg, None, e)?,
+                    window,
+                ));
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        let start = match (offset, decoder.total_duration()) {
+            (Some(offset), _) => samples_of(offset),
+            (None, Some(total)) => (samples_of(total) / 2).saturating_sub(window / 2),
+            // Without a container duration, the middle is only known once decoded
+            (None, None) => {
+                on_samples(middle(&Self::decode_file(file_path, ffmpeg)?, window));
+                return Ok(());
+            }
+        };
 
-        if raw_pcm_samples_slice.len() > 12 * 16000 {
-            let middle = raw_pcm_samples.len() / 2;
+        // The resampler starts over on an input frame every `to / gcd` output samples, so
+        // skipping whole periods of input frames before resampling gives the same output.
+        // Seeking is not used since positions are rounded, WAV ones being computed in f32.
+        let mut skipped = 0;
+        if decoder.current_frame_len().is_none() {
+            #[allow(clippy::cast_possible_truncation)]
+            let (from, to) = (decoder.sample_rate() as usize, SAMPLE_RATE);
+            let (mut gcd, mut remainder) = (from, to);
+            while remainder != 0 {
+                (gcd, remainder) = (remainder, gcd % remainder);
+            }
+            let periods = start / (to / gcd);
+            let samples = periods * (from / gcd) * usize::from(decoder.channels());
+            decoder.by_ref().take(samples).for_each(drop);
+            skipped = periods * (to / gcd);
+        }
 
-            raw_pcm_samples_slice =
-                &raw_pcm_samples_slice[middle - (6 * 16000)..middle + (6 * 16000)];
+        #[allow(clippy::cast_possible_truncation)]
+        let mut converted_window =
+            rodio::source::UniformSourceIterator::<_, i16>::new(decoder, 1, SAMPLE_RATE as u32)
+                .skip(start - skipped)
+                .take(window);
+
+        let mut chunk = Vec::with_capacity(STREAM_CHUNK);
+        loop {
+            chunk.clear();
+            chunk.extend(converted_window.by_ref().take(STREAM_CHUNK));
+            if chunk.is_empty() {
+                return Ok(());
+            }
+            on_samples(&chunk);
         }
+    }
 
-        Ok(SignatureGenerator::make_signature_from_buffer(
-            &raw_pcm_samples_slice[..slice_len],
-        ))
+    pub fn make_signature_from_file(
+        file_path: &str,
+        ffmpeg: Option<&str>,
+    ) -> Result<DecodedSignature, CriticalErrorKind> {
+        // Take 12 seconds from the middle of the file in order to increase recognition odds.
+
+        Self::make_signature_from_window(
+            file_path,
+            ffmpeg,
+            None,
+            Duration::from_secs(SIGNATURE_SECONDS as u64),
+        )
     }
 
-    #[allow(clippy::cast_possible_truncation)]
+    /// Signature of a window of a file, from its middle when no offset is given
+    pub fn make_signature_from_window(
+        file_path: &str,
+        ffmpeg: Option<&str>,
+        offset: Option<Duration>,
+        duration: Duration,
+    ) -> Result<DecodedSignature, CriticalErrorKind> {
+        let mut generator = SignatureGenerator::default();
+        Self::stream_file_window(file_path, ffmpeg, offset, duration, &mut |samples| {
+            generator.feed(samples);
+        })?;
+        Ok(generator.signature())
+    }
+
+    /// Signatures of windows sliding over the samples, with their offset in samples
     #[must_use]
-    pub fn make_signature_from_buffer(s16_mono_16khz_buffer: &[i16]) -> DecodedSignature {
-        let mut this = SignatureGenerator {
-            ring_buffer_of_samples: vec![0i16; 2048],
-            ring_buffer_of_samples_index: 0,
+    pub fn make_signatures_from_buffer(
+        s16_mono_16khz_buffer: &[i16],
+        window: usize,
+        step: usize,
+    ) -> Vec<(usize, DecodedSignature)> {
+        let mut signatures = Vec::new();
+        let mut offset = 0;
+        loop {
+            let end = (offset + window).min(s16_mono_16khz_buffer.len());
+            signatures.push((
+                offset,
+                Self::make_signature_from_buffer(&s16_mono_16khz_buffer[offset..end]),
+            ));
+            offset += step.max(1);
+            if end == s16_mono_16khz_buffer.len() || offset >= s16_mono_16khz_buffer.len() {
+                break;
+            }
+        }
+        signatures
+    }
 
-            reordered_ring_buffer_of_samples: vec![0.0f32; 2048],
+    #[must_use]
+    pub fn make_signature_from_buffer(s16_mono_16khz_buffer: &[i16]) -> DecodedSignature {
+        let mut this = SignatureGenerator::default();
+        this.feed(s16_mono_16khz_buffer);
+        this.signature()
+    }
 
-            fft_outputs: vec![vec![0.0f32; 1025]; 256],
-            fft_outputs_index: 0,
+    /// Process mono 16 KHz samples as they arrive, in chunks of any size
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn feed(&mut self, s16_mono_16khz_samples: &[i16]) {
+        self.signature.number_samples += s16_mono_16khz_samples.len() as u32;
+
+        let mut samples = s16_mono_16khz_samples;
+        if !self.pending_samples.is_empty() {
+            let missing = (128 - self.pending_samples.len()).min(samples.len());
+            self.pending_samples.extend_from_slice(&samples[..missing]);
+            samples = &samples[missing..];
+            if self.pending_samples.len() < 128 {
+                return;
+            }
+            let chunk = std::mem::take(&mut self.pending_samples);
+            self.process_chunk(&chunk);
+        }
 
-            fft_object: RFft1D::<f32>::new(2048),
+        let mut chunks = samples.chunks_exact(128);
+        for chunk in &mut chunks {
+            self.process_chunk(chunk);
+        }
+        self.pending_samples.extend_from_slice(chunks.remainder());
+    }
 
-            spread_fft_outputs: vec![vec![0.0f32; 1025]; 256],
-            spread_fft_outputs_index: 0,
+    /// Samples fed to the current signature
+    #[must_use]
+    pub fn number_samples(&self) -> u32 {
+        self.signature.number_samples
+    }
 
-            num_spread_ffts_done: 0,
+    /// Signature of all samples fed
+    #[must_use]
+    pub fn signature(self) -> DecodedSignature {
+        self.signature
+    }
 
-            signature: DecodedSignature {
+    /// Signature of samples fed since the previous one, the FFT state being kept
+    /// so that the next signature has no warm up
+    pub fn take_signature(&mut self) -> DecodedSignature {
+        self.signature_start_pass = self.num_spread_ffts_done;
+        std::mem::replace(
+            &mut self.signature,
+            DecodedSignature {
                 sample_rate_hz: 16000,
-                number_samples: s16_mono_16khz_buffer.len() as u32,
+                number_samples: 0,
                 frequency_band_to_sound_peaks: HashMap::new(),
             },
-        };
+        )
+    }
 
-        for chunk in s16_mono_16khz_buffer.chunks_exact(128) {
-            this.do_fft(chunk);
+    fn process_chunk(&mut self, chunk: &[i16]) {
+        self.do_fft(chunk);
 
-            this.do_peak_spreading();
+        self.do_peak_spreading();
 
-            this.num_spread_ffts_done += 1;
+        self.num_spread_ffts_done += 1;
 
-            if this.num_spread_ffts_done >= 46 {
-                this.do_peak_recognition();
-            }
+        if self.num_spread_ffts_done >= 46 {
+            self.do_peak_recognition();
         }
-
-        this.signature
     }
 
     #[allow(clippy::cast_precision_loss)]
@@ -249,7 +525,12 @@ impl SignatureGenerator {
                     if fft_minus_46[bin_position] > max_neighbor_in_other_adjacent_ffts {
                         // This is a peak, store the peak
 
-                        let fft_pass_number = self.num_spread_ffts_done - 46;
+                        // Peaks of a previous signature are dropped in rolling mode
+                        let Some(fft_pass_number) =
+                            (self.num_spread_ffts_done - 46).checked_sub(self.signature_start_pass)
+                        else {
+                            continue;
+                        };
 
                         let peak_magnitude: f32 =
                             fft_minus_46[bin_position].ln().max(1.0 / 64.0) * 1477.3 + 6144.0;
@@ -314,3 +595,112 @@ impl SignatureGenerator {
         }
     }
 }
+
+#[test]
+#[allow(clippy::cast_possible_truncation)]
+fn window_decoding_tests() {
+    use std::io::Write;
+
+    // Stereo 44.1 KHz tone rising from 400 Hz, resampled in periods of 441 frames
+    let rate = 44100;
+    let samples = (0..rate * 30)
+        .flat_map(|n| {
+            let t = f64::from(n) / f64::from(rate);
+            let sample =
+                ((2.0 * std::f64::consts::PI * (400.0 + 50.0 * t) * t).sin() * 8000.0) as i16;
+            [sample, sample / 2]
+        })
+        .collect::<Vec<i16>>();
+    let mut wav = tempfile::Builder::new().suffix(".wav").tempfile().unwrap();
+    wav.write_all(&crate::test_helpers::wav(2, rate, &samples))
+        .unwrap();
+    let path = wav.path().to_str().unwrap();
+
+    let whole = SignatureGenerator::decode_file(path, None).unwrap();
+    let window = Duration::from_secs(SIGNATURE_SECONDS as u64);
+    let middle_start = whole.len() / 2 - samples_of(window) / 2;
+    for (offset, start) in [
+        (None, middle_start),
+        (
+            Some(Duration::from_millis(7321)),
+            samples_of(Duration::from_millis(7321)),
+        ),
+        (
+            Some(Duration::from_secs(25)),
+            samples_of(Duration::from_secs(25)),
+        ),
+        (Some(Duration::from_secs(40)), whole.len()),
+    ] {
+        let end = (start + samples_of(window)).min(whole.len());
+        let samples = SignatureGenerator::decode_file_window(path, None, offset, window).unwrap();
+        assert_eq!(samples, whole[start..end], "{offset:?}");
+    }
+
+    assert_eq!(
+        SignatureGenerator::make_signature_from_file(path, None)
+            .unwrap()
+            .encode_to_binary()
+            .unwrap(),
+        SignatureGenerator::make_signature_from_buffer(middle(&whole, samples_of(window)))
+            .encode_to_binary()
+            .unwrap()
+    );
+
+    // MP3 frames of noise, for windows not to start on a frame
+    let mut mp3 = tempfile::Builder::new().suffix(".mp3").tempfile().unwrap();
+    mp3.write_all(&crate::test_helpers::mp3(600, 1)).unwrap();
+    let path = mp3.path().to_str().unwrap();
+    let whole = SignatureGenerator::decode_file(path, None).unwrap();
+    let offset = Duration::from_millis(2345);
+    let start = samples_of(offset);
+    let samples = SignatureGenerator::decode_file_window(path, None, Some(offset), window).unwrap();
+    assert!(samples.iter().any(|sample| *sample != 0));
+    assert_eq!(samples, whole[start..start + samples_of(window)]);
+    assert_eq!(
+        SignatureGenerator::make_signature_from_window(path, None, Some(offset), window)
+            .unwrap()
+            .encode_to_binary()
+            .unwrap(),
+        SignatureGenerator::make_signature_from_buffer(&whole[start..start + samples_of(window)])
+            .encode_to_binary()
+            .unwrap()
+    );
+}
+
+#[test]
+#[allow(clippy::cast_possible_truncation)]
+fn streaming_signature_tests() {
+    // Tone rising from 400 Hz, giving peaks over the frequency bands
+    let samples = (0..16000 * 6)
+        .map(|n| {
+            let t = f64::from(n) / 16000.0;
+            ((2.0 * std::f64::consts::PI * (400.0 + 300.0 * t) * t).sin() * 8000.0) as i16
+        })
+        .collect::<Vec<i16>>();
+    let whole = SignatureGenerator::make_signature_from_buffer(&samples);
+
+    let mut generator = SignatureGenerator::default();
+    for chunk in samples.chunks(1000) {
+        generator.feed(chunk);
+    }
+    assert_eq!(generator.number_samples(), 16000 * 6);
+    assert_eq!(
+        generator.signature().encode_to_binary().unwrap(),
+        whole.encode_to_binary().unwrap()
+    );
+
+    let mut generator = SignatureGenerator::default();
+    generator.feed(&samples[..16000 * 3]);
+    let first = generator.take_signature();
+    generator.feed(&samples[16000 * 3..]);
+    let second = generator.take_signature();
+    assert_eq!(first.number_samples + second.number_samples, 16000 * 6);
+    assert!(!second.frequency_band_to_sound_peaks.is_empty());
+    assert!(
+        second
+            .frequency_band_to_sound_peaks
+            .values()
+            .flatten()
+            .all(|peak| peak.fft_pass_number < 16000 * 3 / 128)
+    );
+}
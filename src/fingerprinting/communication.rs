@@ -4,61 +4,175 @@ use crate::music::errors::CriticalErrorKind;
 use rand::seq::IndexedRandom;
 use reqwest::header::HeaderMap;
 use serde_json::{Value, json};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 use std::time::SystemTime;
 use uuid::Uuid;
 
-#[allow(clippy::cast_possible_truncation, clippy::missing_panics_doc)]
-pub async fn recognize_song_from_signature(
-    signature: &DecodedSignature,
-) -> Result<Value, CriticalErrorKind> {
-    let timestamp_ms = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)?
-        .as_millis();
-
-    let post_data = json!({
-        "geolocation": {
-            "altitude": 300,
-            "latitude": 45,
-            "longitude": 2
-        },
-        "signature": {
-            "samplems": u32::try_from((f64::from(signature.number_samples) / f64::from(signature.sample_rate_hz) * 1000.) as i64)?,
-            "timestamp": (timestamp_ms % u128::from(u32::MAX)) as u32,
-            "uri": signature.encode_to_uri()?
-        },
-        "timestamp": timestamp_ms as u32,
-        "timezone": "Europe/Paris"
-    });
-
-    let uuid_1 = Uuid::new_v4().hyphenated().to_string().to_uppercase();
-    let uuid_2 = Uuid::new_v4().hyphenated().to_string();
-    let url = format!("https://amp.shazam.com/discovery/v5/en/US/android/-/tag/{uuid_1}/{uuid_2}");
-
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        "User-Agent",
-        USER_AGENTS.choose(&mut rand::rng()).unwrap().parse()?,
+pub const DEFAULT_SHAZAM_ENDPOINT: &str = "https://amp.shazam.com/discovery/v5/en/US/android/-/tag";
+pub const DEFAULT_LOCALE: &str = "en-US";
+pub const DEFAULT_TIMEZONE: &str = "Europe/Paris";
+pub const DEFAULT_TIMEOUT: &str = "20s";
+
+/// Service answering Shazam-like JSON responses to signatures
+#[async_trait::async_trait]
+pub trait Recognizer: Send + Sync {
+    async fn recognize(&self, signature: &DecodedSignature) -> Result<Value, CriticalErrorKind>;
+}
+
+/// Shazam Android client API
+pub struct ShazamRecognizer {
+    endpoint: String,
+    locale: String,
+    timezone: String,
+    client: reqwest::Client,
+}
+
+impl ShazamRecognizer {
+    pub fn new(
+        endpoint: &str,
+        locale: &str,
+        timezone: &str,
+        timeout: Duration,
+        proxy: Option<&str>,
+    ) -> Result<Self, CriticalErrorKind> {
+        let mut client = reqwest::Client::builder().timeout(timeout);
+        if let Some(proxy) = proxy {
+            client = client.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        Ok(Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            locale: locale.to_string(),
+            timezone: timezone.to_string(),
+            client: client.build()?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Recognizer for ShazamRecognizer {
+    #[allow(clippy::cast_possible_truncation)]
+    async fn recognize(&self, signature: &DecodedSignature) -> Result<Value, CriticalErrorKind> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_millis();
+
+        let post_data = json!({
+            "geolocation": {
+                "altitude": 300,
+                "latitude": 45,
+                "longitude": 2
+            },
+            "signature": {
+                "samplems": u32::try_from((f64::from(signature.number_samples) / f64::from(signature.sample_rate_hz) * 1000.) as i64)?,
+                "timestamp": (timestamp_ms % u128::from(u32::MAX)) as u32,
+                "uri": signature.encode_to_uri()?
+            },
+            "timestamp": timestamp_ms as u32,
+            "timezone": self.timezone
+        });
+
+        let uuid_1 = Uuid::new_v4().hyphenated().to_string().to_uppercase();
+        let uuid_2 = Uuid::new_v4().hyphenated().to_string();
+        let url = format!("{}/{uuid_1}/{uuid_2}", self.endpoint);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "User-Agent",
+            USER_AGENTS
+                .choose(&mut rand::rng())
+                .unwrap_or(&USER_AGENTS[0])
+                .parse()?,
+        );
+        headers.insert("Content-Language", self.locale.replace('-', "_").parse()?);
+
+        let response = self
+            .client
+            .post(&url)
+            .json(&post_data)
+            .query(&[
+                ("sync", "true"),
+                ("webv3", "true"),
+                ("sampling", "true"),
+                ("connected", ""),
+                ("shazamapiversion", "v3"),
+                ("sharehub", "true"),
+                ("video", "v3"),
+            ])
+            .headers(headers)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json().await?)
+    }
+}
+
+/// Recorded responses, a JSON array of them or a single one, replayed in order
+pub struct ReplayRecognizer {
+    path: String,
+    responses: Vec<Value>,
+    next: AtomicUsize,
+}
+
+impl ReplayRecognizer {
+    pub fn new(path: &str) -> Result<Self, CriticalErrorKind> {
+        let responses = match serde_json::from_slice(&std::fs::read(path)?)? {
+            Value::Array(responses) => responses,
+            response => vec![response],
+        };
+        Ok(Self {
+            path: path.to_string(),
+            responses,
+            next: AtomicUsize::new(0),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Recognizer for ReplayRecognizer {
+    async fn recognize(&self, _signature: &DecodedSignature) -> Result<Value, CriticalErrorKind> {
+        let index = self.next.fetch_add(1, Ordering::SeqCst);
+        self.responses
+            .get(index)
+            .cloned()
+            .ok_or_else(|| CriticalErrorKind::ReplayExhausted {
+                path: self.path.clone(),
+                responses: self.responses.len(),
+            })
+    }
+}
+
+#[tokio::test]
+async fn replay_recognizer_tests() {
+    let signature =
+        crate::fingerprinting::algorithm::SignatureGenerator::make_signature_from_buffer(
+            &vec![0; 16000],
+        );
+    let responses = tempfile::NamedTempFile::new().unwrap();
+    let path = responses.path().to_str().unwrap();
+
+    std::fs::write(
+        path,
+        r#"[{"matches": []}, {"track": {"title": "Thunderstruck"}}]"#,
+    )
+    .unwrap();
+    let recognizer = ReplayRecognizer::new(path).unwrap();
+    assert_eq!(
+        recognizer.recognize(&signature).await.unwrap(),
+        json!({"matches": []})
     );
-    headers.insert("Content-Language", "en_US".parse()?);
-
-    let client = reqwest::Client::new();
-    let response = client
-        .post(&url)
-        .json(&post_data)
-        .timeout(Duration::from_secs(20))
-        .query(&[
-            ("sync", "true"),
-            ("webv3", "true"),
-            ("sampling", "true"),
-            ("connected", ""),
-            ("shazamapiversion", "v3"),
-            ("sharehub", "true"),
-            ("video", "v3"),
-        ])
-        .headers(headers)
-        .send()
-        .await?;
-
-    Ok(response.json().await?)
+    assert_eq!(
+        recognizer.recognize(&signature).await.unwrap()["track"]["title"],
+        "Thunderstruck"
+    );
+    assert!(matches!(
+        recognizer.recognize(&signature).await,
+        Err(CriticalErrorKind::ReplayExhausted { responses: 2, .. })
+    ));
+
+    std::fs::write(path, r#"{"matches": []}"#).unwrap();
+    let recognizer = ReplayRecognizer::new(path).unwrap();
+    assert!(recognizer.recognize(&signature).await.is_ok());
+    assert!(ReplayRecognizer::new("missing.json").is_err());
 }
@@ -1,82 +1,232 @@
-use std::io::BufReader;
+use crate::music::errors::CriticalErrorKind;
+use std::io::Read;
+use std::time::Duration;
 
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 
-use std::process::Command;
+use std::process::{Command, Stdio};
 
-/// This function used to decode a file with FFMpeg, if it is installed on
-/// the system, in the case where Rodio can't decode the concerned format
-/// (for example with .WMA, .M4A, etc.).
-pub fn decode_with_ffmpeg(file_path: &str) -> Option<rodio::Decoder<BufReader<std::fs::File>>> {
-    // Find the path for FFMpeg, in the case where it is installed
+/// Bytes of FFMpeg output read at once
+const READ_CHUNK: usize = 64 * 1024;
 
-    let mut possible_ffmpeg_paths: Vec<&str> = vec!["ffmpeg", "ffmpeg.exe"];
+/// Find the path for FFMpeg, the configured one or else one installed on the system
+pub fn find_ffmpeg(configured_path: Option<&str>) -> Option<String> {
+    let mut possible_ffmpeg_paths: Vec<String> = match configured_path {
+        Some(path) => vec![path.to_string()],
+        None => vec!["ffmpeg".to_string(), "ffmpeg.exe".to_string()],
+    };
 
-    let mut current_dir_ffmpeg_path = std::env::current_exe().unwrap();
-    current_dir_ffmpeg_path.pop();
-    current_dir_ffmpeg_path.push("ffmpeg.exe");
-
-    possible_ffmpeg_paths.push(current_dir_ffmpeg_path.to_str().unwrap());
-
-    let mut actual_ffmpeg_path: Option<&str> = None;
+    if configured_path.is_none()
+        && let Ok(mut current_dir_ffmpeg_path) = std::env::current_exe()
+    {
+        current_dir_ffmpeg_path.pop();
+        current_dir_ffmpeg_path.push("ffmpeg.exe");
+        possible_ffmpeg_paths.push(current_dir_ffmpeg_path.to_string_lossy().to_string());
+    }
 
     for possible_path in possible_ffmpeg_paths {
         // Use .output() to execute the subprocess testing for FFMpeg
         // presence and correct execution, so that it does not pollute
         // the standard or error output in any way
 
-        let mut command = Command::new(possible_path);
+        let mut command = Command::new(&possible_path);
         let command = command.arg("-version");
 
         #[cfg(windows)]
         let command = command.creation_flags(0x00000008); // Set "CREATE_NO_WINDOW" on Windows
 
-        if let Ok(process) = command.output() {
-            if process.status.success() {
-                actual_ffmpeg_path = Some(possible_path);
-                break;
-            }
+        if let Ok(process) = command.output()
+            && process.status.success()
+        {
+            return Some(possible_path);
         }
     }
+    None
+}
 
-    // If FFMpeg is available, use it to convert the input file
-    // from whichever format to a .WAV (because Rodio has its
-    // decoding support limited to .WAV, .FLAC, .OGG, .MP3, which
-    // makes that .MP4/.AAC, .OPUS or .WMA are not supported
-    // by default)
-
-    if let Some(ffmpeg_path) = actual_ffmpeg_path {
-        // Create a sink file for FFMpeg
-
-        let sink_file = tempfile::Builder::new().suffix(".wav").tempfile().unwrap();
-
-        let sink_file_path = sink_file.into_temp_path();
-
-        // Try to convert the input video or audio file to a standard
-        // .WAV s16le PCM file using FFMpeg, and pass it to Rodio
-        // later in the case where it succeeded
+/// This function used to decode a file with FFMpeg to mono 16 KHz PCM
+/// samples, in the case where Rodio can't decode the concerned format
+/// (for example with .WMA, .M4A, etc.). FFMpeg seeks to the window
+/// start and stops at its end, when one is given. Samples are given to
+/// a callback in chunks while FFMpeg decodes, without buffering its output.
+pub fn stream_with_ffmpeg(
+    ffmpeg_path: &str,
+    file_path: &str,
+    window: Option<(Duration, Duration)>,
+    on_samples: &mut dyn FnMut(&[i16]),
+) -> Result<(), CriticalErrorKind> {
+    // Let FFMpeg write raw s16le PCM to its standard output, read
+    // without going through a temporary file
+
+    let mut command = Command::new(ffmpeg_path);
+    command.args(["-v", "error", "-nostdin"]);
+    if let Some((offset, _)) = window {
+        command.args(["-ss", &format!("{:.3}", offset.as_secs_f64())]);
+    }
+    command.args(["-i", file_path]);
+    if let Some((_, duration)) = window {
+        command.args(["-t", &format!("{:.3}", duration.as_secs_f64())]);
+    }
+    command.args(["-vn", "-ac", "1", "-ar", "16000", "-f", "s16le", "-"]);
+
+    // Set "CREATE_NO_WINDOW" on Windows, see
+    // https://stackoverflow.com/a/60958956/662399
+    #[cfg(windows)]
+    command.creation_flags(0x00000008);
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let (Some(mut stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(std::io::Error::other("FFMpeg output not captured").into());
+    };
+    // Errors are read aside, FFMpeg blocking on a full pipe otherwise
+    let errors = std::thread::spawn(move || {
+        let mut errors = Vec::new();
+        let _ = stderr.read_to_end(&mut errors);
+        String::from_utf8_lossy(&errors).trim().to_string()
+    });
+    // When reading stops early, FFMpeg is killed and waited for, not to be left running
+    let running = scopeguard::guard((child, errors), |(mut child, errors)| {
+        let _ = child.kill();
+        let _ = child.wait();
+        let _ = errors.join();
+    });
+
+    // A sample may be split between two reads, its first byte being kept for the next one
+    let mut buffer = vec![0u8; READ_CHUNK];
+    let mut samples = Vec::with_capacity(READ_CHUNK / 2);
+    let mut kept = 0;
+    loop {
+        let read = match stdout.read(&mut buffer[kept..]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let end = kept + read;
+        let complete = end - end % 2;
+        samples.clear();
+        samples.extend(
+            buffer[..complete]
+                .chunks_exact(2)
+                .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]])),
+        );
+        on_samples(&samples);
+        buffer.copy_within(complete..end, 0);
+        kept = end - complete;
+    }
 
-        let mut command = Command::new(ffmpeg_path);
+    let (mut child, errors) = scopeguard::ScopeGuard::into_inner(running);
+    let status = child.wait()?;
+    let errors = errors.join().unwrap_or_default();
+    if !status.success() {
+        return Err(CriticalErrorKind::FfmpegDecodeError {
+            path: file_path.to_string(),
+            error: errors,
+        });
+    }
+    Ok(())
+}
 
-        let command = command.args(["-y", "-i", file_path, sink_file_path.to_str().unwrap()]);
+/// Errors FFMpeg reports while decoding a whole file, discarding the output
+pub fn decoding_errors(
+    ffmpeg_path: &str,
+    file_path: &str,
+) -> Result<Vec<String>, CriticalErrorKind> {
+    let mut command = Command::new(ffmpeg_path);
+    command.args([
+        "-v", "error", "-nostdin", "-i", file_path, "-f", "null", "-",
+    ]);
+
+    #[cfg(windows)]
+    command.creation_flags(0x00000008); // Set "CREATE_NO_WINDOW" on Windows
+
+    let output = command.output()?;
+    let mut errors = String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    if !output.status.success() && errors.is_empty() {
+        errors.push(format!("FFMpeg exited with {}", output.status));
+    }
+    Ok(errors)
+}
 
-        // Set "CREATE_NO_WINDOW" on Windows, see
-        // https://stackoverflow.com/a/60958956/662399
-        #[cfg(windows)]
-        let command = command.creation_flags(0x00000008);
-
-        if let Ok(process) = command.output() {
-            if process.status.success() {
-                return Some(
-                    rodio::Decoder::new(BufReader::new(
-                        std::fs::File::open(sink_file_path.to_str().unwrap()).unwrap(),
-                    ))
-                    .unwrap(),
-                );
-            }
-        }
+#[test]
+fn ffmpeg_tests() {
+    use crate::fingerprinting::algorithm::SignatureGenerator;
+    use std::io::Write;
+
+    let missing_ffmpeg = "/nonexistent/ffmpeg";
+    assert!(find_ffmpeg(Some(missing_ffmpeg)).is_none());
+    assert!(matches!(
+        stream_with_ffmpeg(missing_ffmpeg, "song.wma", None, &mut |_| ()),
+        Err(CriticalErrorKind::IOError(_))
+    ));
+
+    let mut unsupported = tempfile::Builder::new().suffix(".wma").tempfile().unwrap();
+    unsupported.write_all(b"not audio").unwrap();
+    let path = unsupported.path().to_str().unwrap();
+    assert!(matches!(
+        SignatureGenerator::decode_file(path, Some(missing_ffmpeg)),
+        Err(CriticalErrorKind::FfmpegNotFound)
+    ));
+    if find_ffmpeg(None).is_none() {
+        assert!(matches!(
+            SignatureGenerator::decode_file(path, None),
+            Err(CriticalErrorKind::DecoderError(_))
+        ));
     }
 
-    None
+    // Fake FFMpeg writing samples in several writes, one of them split, then failing
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = tempfile::tempdir().unwrap();
+        let fake_ffmpeg = directory.path().join("ffmpeg");
+        std::fs::write(
+            &fake_ffmpeg,
+            "#!/bin/sh\nprintf '\\001\\000\\002'\nsleep 0.1\nprintf '\\000\\377\\377'\n\
+             echo 'decoding failed' >&2\nexit 1\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&fake_ffmpeg, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let fake_ffmpeg = fake_ffmpeg.to_str().unwrap();
+
+        let mut chunks = Vec::new();
+        let result = stream_with_ffmpeg(fake_ffmpeg, "song.wma", None, &mut |samples| {
+            chunks.push(samples.to_vec());
+        });
+        assert_eq!(chunks.concat(), [1, 2, -1]);
+        assert!(chunks.len() > 1);
+        assert!(matches!(
+            result,
+            Err(CriticalErrorKind::FfmpegDecodeError { error, .. }) if error == "decoding failed"
+        ));
+
+        // Fake FFMpeg still decoding when its output is no longer read
+        let slow_ffmpeg = directory.path().join("slow_ffmpeg");
+        std::fs::write(
+            &slow_ffmpeg,
+            "#!/bin/sh\nprintf '\\001\\000'\nexec sleep 30\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&slow_ffmpeg, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let started = std::time::Instant::now();
+        let result = std::panic::catch_unwind(|| {
+            stream_with_ffmpeg(slow_ffmpeg.to_str().unwrap(), "song.wma", None, &mut |_| {
+                panic!("samples rejected")
+            })
+        });
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
 }
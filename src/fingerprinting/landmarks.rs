@@ -0,0 +1,41 @@
+use crate::fingerprinting::signature_format::DecodedSignature;
+
+/// Peaks following an anchor which are paired with it
+const FAN_OUT: usize = 8;
+/// Maximum distance in FFT passes between paired peaks, fitting in 12 bits
+const MAX_DELTA: u32 = 4095;
+/// Frequency bins are corrected by 64 steps, 10 bits are kept per frequency
+const FREQUENCY_SHIFT: u32 = 6;
+
+/// Hash of a pair of peaks, with the FFT pass of its anchor peak
+pub type Landmark = (u32, u32);
+
+/// Landmarks pairing each peak with the following ones, whatever their frequency band
+#[must_use]
+pub fn landmarks(signature: &DecodedSignature) -> Vec<Landmark> {
+    let mut peaks = signature
+        .frequency_band_to_sound_peaks
+        .values()
+        .flatten()
+        .map(|peak| {
+            (
+                peak.fft_pass_number,
+                u32::from(peak.corrected_peak_frequency_bin) >> FREQUENCY_SHIFT,
+            )
+        })
+        .collect::<Vec<_>>();
+    peaks.sort_unstable();
+
+    let mut landmarks = Vec::with_capacity(peaks.len() * FAN_OUT);
+    for (index, (anchor_pass, anchor_frequency)) in peaks.iter().enumerate() {
+        for (pass, frequency) in peaks.iter().skip(index + 1).take(FAN_OUT) {
+            let delta = pass - anchor_pass;
+            if delta > MAX_DELTA {
+                break;
+            }
+            let hash = ((anchor_frequency & 0x3ff) << 22) | ((frequency & 0x3ff) << 12) | delta;
+            landmarks.push((hash, *anchor_pass));
+        }
+    }
+    landmarks
+}
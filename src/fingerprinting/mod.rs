@@ -1,6 +1,10 @@
 pub mod algorithm;
 pub mod communication;
-mod ffmpeg_wrapper;
-mod hanning;
+#[cfg(feature = "ffmpeg")]
+pub(crate) mod ffmpeg_wrapper;
+pub(crate) mod hanning;
+pub mod landmarks;
+pub mod pcm;
 pub mod signature_format;
+pub mod spectrum;
 mod user_agent;
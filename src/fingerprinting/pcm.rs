@@ -0,0 +1,144 @@
+use crate::music::errors::CriticalErrorKind;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{BufRead, BufReader, Read};
+use std::time::Duration;
+
+/// Signed 16 bits little endian PCM read from a stream, either raw or with a WAV header
+pub struct PcmSource<R: Read> {
+    reader: BufReader<R>,
+    channels: u16,
+    sample_rate: u32,
+    /// Bytes left in the WAV data chunk
+    remaining: Option<u64>,
+}
+
+impl<R: Read> PcmSource<R> {
+    /// Raw PCM is described by the given channels and sample rate, a WAV header overriding them
+    pub fn new(reader: R, channels: u16, sample_rate: u32) -> Result<Self, CriticalErrorKind> {
+        let mut source = PcmSource {
+            reader: BufReader::new(reader),
+            channels,
+            sample_rate,
+            remaining: None,
+        };
+        if source.reader.fill_buf()?.starts_with(b"RIFF") {
+            source.read_wav_header()?;
+        }
+        Ok(source)
+    }
+
+    fn read_wav_header(&mut self) -> Result<(), CriticalErrorKind> {
+        let mut id = [0u8; 4];
+        self.reader.read_exact(&mut id)?;
+        self.reader.read_u32::<LittleEndian>()?;
+        self.reader.read_exact(&mut id)?;
+        if &id != b"WAVE" {
+            return Err(CriticalErrorKind::UnsupportedFormat(
+                "RIFF stream which is not WAVE".to_string(),
+            ));
+        }
+        loop {
+            self.reader.read_exact(&mut id)?;
+            let size = u64::from(self.reader.read_u32::<LittleEndian>()?);
+            match &id {
+                b"fmt " => {
+                    let format = self.reader.read_u16::<LittleEndian>()?;
+                    self.channels = self.reader.read_u16::<LittleEndian>()?;
+                    self.sample_rate = self.reader.read_u32::<LittleEndian>()?;
+                    self.reader.read_u32::<LittleEndian>()?; // byte rate
+                    self.reader.read_u16::<LittleEndian>()?; // block align
+                    let bits = self.reader.read_u16::<LittleEndian>()?;
+                    // PCM, or extensible format whose sub format is assumed to be PCM
+                    if !(format == 1 || format == 0xfffe) || bits != 16 {
+                        return Err(CriticalErrorKind::UnsupportedFormat(format!(
+                            "WAVE format {format} with {bits} bits samples"
+                        )));
+                    }
+                    self.skip(size + size % 2 - 16)?;
+                }
+                b"data" => {
+                    // Streaming writers leave the size unknown
+                    if size != 0 && size != u64::from(u32::MAX) {
+                        self.remaining = Some(size);
+                    }
+                    return Ok(());
+                }
+                _ => self.skip(size + size % 2)?,
+            }
+        }
+    }
+
+    fn skip(&mut self, bytes: u64) -> Result<(), CriticalErrorKind> {
+        std::io::copy(&mut self.reader.by_ref().take(bytes), &mut std::io::sink())?;
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for PcmSource<R> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining = remaining.checked_sub(2)?;
+        }
+        self.reader.read_i16::<LittleEndian>().ok()
+    }
+}
+
+impl<R: Read> rodio::Source for PcmSource<R> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[test]
+fn pcm_source_tests() {
+    use byteorder::WriteBytesExt;
+    use std::io::Write;
+
+    let samples = [1i16, -1, 2, -2, 3, -3];
+    let mut wav = Vec::new();
+    wav.write_all(b"RIFF").unwrap();
+    wav.write_u32::<LittleEndian>(0).unwrap();
+    wav.write_all(b"WAVELIST").unwrap();
+    wav.write_u32::<LittleEndian>(3).unwrap();
+    wav.write_all(&[0, 0, 0, 0]).unwrap();
+    wav.write_all(b"fmt ").unwrap();
+    wav.write_u32::<LittleEndian>(16).unwrap();
+    wav.write_u16::<LittleEndian>(1).unwrap();
+    wav.write_u16::<LittleEndian>(2).unwrap();
+    wav.write_u32::<LittleEndian>(44100).unwrap();
+    wav.write_u32::<LittleEndian>(44100 * 4).unwrap();
+    wav.write_u16::<LittleEndian>(4).unwrap();
+    wav.write_u16::<LittleEndian>(16).unwrap();
+    wav.write_all(b"data").unwrap();
+    wav.write_u32::<LittleEndian>(8).unwrap();
+    for sample in samples {
+        wav.write_i16::<LittleEndian>(sample).unwrap();
+    }
+
+    let source = PcmSource::new(wav.as_slice(), 1, 16000).unwrap();
+    assert_eq!(rodio::Source::channels(&source), 2);
+    assert_eq!(rodio::Source::sample_rate(&source), 44100);
+    assert_eq!(source.collect::<Vec<_>>(), vec![1, -1, 2, -2]);
+
+    let raw = samples
+        .iter()
+        .flat_map(|sample| sample.to_le_bytes())
+        .collect::<Vec<_>>();
+    let source = PcmSource::new(raw.as_slice(), 1, 16000).unwrap();
+    assert_eq!(rodio::Source::sample_rate(&source), 16000);
+    assert_eq!(source.collect::<Vec<_>>(), samples);
+}
@@ -30,7 +30,19 @@ impl Ord for FrequencyBand {
 
 impl PartialOrd for FrequencyBand {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some((*self as i32).cmp(&(*other as i32)))
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for FrequencyBand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let range = match self {
+            FrequencyBand::_250_520 => "250-520",
+            FrequencyBand::_520_1450 => "520-1450",
+            FrequencyBand::_1450_3500 => "1450-3500",
+            FrequencyBand::_3500_5500 => "3500-5500",
+        };
+        write!(f, "{range} Hz")
     }
 }
 
@@ -197,6 +209,19 @@ impl DecodedSignature {
         })
     }
 
+    /// Decode either a binary signature or a data URI, as read from a file
+    pub fn decode_from_data(data: &[u8]) -> Result<Self, CriticalErrorKind> {
+        if DecodedSignature::is_uri(data) {
+            return DecodedSignature::decode_from_uri(String::from_utf8_lossy(data).trim());
+        }
+        DecodedSignature::decode_from_binary(data)
+    }
+
+    #[must_use]
+    pub fn is_uri(data: &[u8]) -> bool {
+        data.starts_with(DATA_URI_PREFIX.as_bytes())
+    }
+
     pub fn decode_from_uri(uri: &str) -> Result<Self, CriticalErrorKind> {
         if !uri.starts_with(DATA_URI_PREFIX) {
             return Err(CriticalErrorKind::InvalidURI(uri.to_string()));
@@ -0,0 +1,221 @@
+use crate::fingerprinting::hanning::HANNING_WINDOW_2048_MULTIPLIERS;
+use crate::music::errors::CriticalErrorKind;
+use chfft::RFft1D;
+use rodio::Source;
+use serde::{Serialize, Serializer};
+use std::io::BufReader;
+
+const FFT_LEN: usize = 2048;
+const BINS: usize = FFT_LEN / 2 + 1;
+/// Bins averaged on each side when smoothing, and compared on each side of a cutoff
+const SMOOTHING_BINS: usize = 6;
+/// Level drop over a few bins making a hard cutoff
+const CLIFF_DB: f32 = 20.0;
+/// Level above a cutoff, below the reference one, where only noise is left
+const FLOOR_DB: f32 = 40.0;
+/// Cutoffs are only looked for above this frequency
+const MIN_CUTOFF_HZ: f32 = 5000.0;
+/// Reference level band, where music always has content
+const REFERENCE_BAND_HZ: (f32, f32) = (1000.0, 4000.0);
+/// Encoders low pass filters down to this frequency for lossless encodings
+const LOSSLESS_CUTOFF_HZ: f32 = 21000.0;
+/// Lowest sample rate of high resolution files
+const HIGH_RESOLUTION_RATE: u32 = 88200;
+/// Content of upsampled files ends below the Nyquist frequency of their source
+const UPSAMPLED_CUTOFF_HZ: f32 = 24500.0;
+/// Usual low pass frequencies of MP3 encoders, and their bitrate
+const LOSSY_CUTOFFS: [(f32, u16); 6] = [
+    (19500.0, 320),
+    (18500.0, 256),
+    (17500.0, 192),
+    (16500.0, 160),
+    (15500.0, 128),
+    (13500.0, 96),
+];
+const LOWEST_BITRATE: u16 = 64;
+
+/// Likely quality of the source a file was made from
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SourceQuality {
+    Lossless,
+    /// Transcoded from a lossy file of about this bitrate, in kbps
+    Lossy(u16),
+    /// Upsampled from a standard sample rate
+    Upsampled,
+}
+
+/// Serialized as displayed, the way verdicts are stored
+impl Serialize for SourceQuality {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl std::fmt::Display for SourceQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SourceQuality::Lossless => write!(f, "lossless"),
+            SourceQuality::Lossy(kbps) => write!(f, "lossy-{kbps}k"),
+            SourceQuality::Upsampled => write!(f, "upsampled"),
+        }
+    }
+}
+
+/// Power spectrum of a signal, averaged over its windows of 2048 samples
+pub struct Spectrum {
+    pub sample_rate: u32,
+    /// Level of each frequency bin, in dB
+    levels: Vec<f32>,
+}
+
+impl Spectrum {
+    /// Spectrum of mono samples, the last incomplete window being ignored
+    pub fn from_samples(samples: impl Iterator<Item = f32>, sample_rate: u32) -> Self {
+        let mut fft = RFft1D::<f32>::new(FFT_LEN);
+        let mut powers = vec![0.0f64; BINS];
+        let mut windows = 0u32;
+        let mut window = Vec::with_capacity(FFT_LEN);
+        for sample in samples {
+            window.push(sample * HANNING_WINDOW_2048_MULTIPLIERS[window.len()]);
+            if window.len() == FFT_LEN {
+                for (power, bin) in powers.iter_mut().zip(fft.forward(&window)) {
+                    *power += f64::from(bin.norm_sqr());
+                }
+                windows += 1;
+                window.clear();
+            }
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        let levels = powers
+            .into_iter()
+            .map(|power| (10.0 * (power / f64::from(windows.max(1)) + 1e-10).log10()) as f32)
+            .collect();
+        Self {
+            sample_rate,
+            levels,
+        }
+    }
+
+    /// Spectrum of a file, its channels being averaged
+    pub fn from_file(path: &str) -> Result<Self, CriticalErrorKind> {
+        let decoder = rodio::Decoder::new(BufReader::new(std::fs::File::open(path)?))?;
+        let (channels, sample_rate) = (usize::from(decoder.channels()), decoder.sample_rate());
+        let mut frame = Vec::with_capacity(channels);
+        #[allow(clippy::cast_precision_loss)]
+        let samples = decoder.filter_map(move |sample| {
+            frame.push(f32::from(sample));
+            if frame.len() < channels {
+                return None;
+            }
+            let mono = frame.iter().sum::<f32>() / channels as f32;
+            frame.clear();
+            Some(mono)
+        });
+        Ok(Self::from_samples(samples, sample_rate))
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.sample_rate as f32 / FFT_LEN as f32
+    }
+
+    fn bin(&self, frequency: f32) -> usize {
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss
+        )]
+        let bin = (frequency * FFT_LEN as f32 / self.sample_rate as f32) as usize;
+        bin.min(BINS - 1)
+    }
+
+    /// Frequency above which the spectrum drops at once to noise, as a low pass filter leaves
+    #[must_use]
+    pub fn cutoff(&self) -> Option<f32> {
+        #[allow(clippy::cast_precision_loss)]
+        let mean = |levels: &[f32]| levels.iter().sum::<f32>() / levels.len().max(1) as f32;
+        let smoothed = (0..BINS)
+            .map(|bin| {
+                mean(
+                    &self.levels
+                        [bin.saturating_sub(SMOOTHING_BINS)..(bin + SMOOTHING_BINS + 1).min(BINS)],
+                )
+            })
+            .collect::<Vec<_>>();
+        let reference =
+            mean(&smoothed[self.bin(REFERENCE_BAND_HZ.0)..self.bin(REFERENCE_BAND_HZ.1)]);
+
+        let (cutoff, drop) = (self.bin(MIN_CUTOFF_HZ).max(SMOOTHING_BINS)..BINS - SMOOTHING_BINS)
+            .map(|bin| {
+                let below = mean(&smoothed[bin - SMOOTHING_BINS..=bin]);
+                let above = mean(&smoothed[bin + 1..=bin + SMOOTHING_BINS]);
+                (bin, below - above)
+            })
+            .max_by(|(_, left), (_, right)| left.total_cmp(right))?;
+        (drop >= CLIFF_DB && mean(&self.levels[cutoff + 1..]) <= reference - FLOOR_DB)
+            .then(|| self.frequency(cutoff))
+    }
+
+    /// Likely source quality, from the cutoff frequency
+    #[must_use]
+    pub fn source_quality(&self) -> SourceQuality {
+        let Some(cutoff) = self.cutoff() else {
+            return SourceQuality::Lossless;
+        };
+        if self.sample_rate >= HIGH_RESOLUTION_RATE && cutoff <= UPSAMPLED_CUTOFF_HZ {
+            return SourceQuality::Upsampled;
+        }
+        if cutoff >= LOSSLESS_CUTOFF_HZ {
+            return SourceQuality::Lossless;
+        }
+        SourceQuality::Lossy(
+            LOSSY_CUTOFFS
+                .iter()
+                .find(|(frequency, _)| cutoff >= *frequency)
+                .map_or(LOWEST_BITRATE, |(_, kbps)| *kbps),
+        )
+    }
+}
+
+#[test]
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+fn spectrum_tests() {
+    // Tones every 100 Hz up to a low pass frequency, with seeded phases
+    let tones = |sample_rate: u32, low_pass: u32| {
+        let mut state = 1u64;
+        let phases = (100..low_pass)
+            .step_by(100)
+            .map(|frequency| {
+                let random = crate::test_helpers::lcg(&mut state);
+                (
+                    frequency as f32,
+                    (random >> 40) as f32 / (1u64 << 24) as f32 * std::f32::consts::TAU,
+                )
+            })
+            .collect::<Vec<_>>();
+        let spectrum = Spectrum::from_samples(
+            (0..sample_rate).map(|n| {
+                let t = n as f32 / sample_rate as f32;
+                phases
+                    .iter()
+                    .map(|(frequency, phase)| (std::f32::consts::TAU * frequency * t + phase).sin())
+                    .sum::<f32>()
+                    * 100.0
+            }),
+            sample_rate,
+        );
+        (spectrum.cutoff(), spectrum.source_quality())
+    };
+
+    let (cutoff, quality) = tones(44100, 16000);
+    assert!((cutoff.unwrap() - 16000.0).abs() < 200.0, "{cutoff:?}");
+    assert_eq!(quality, SourceQuality::Lossy(128));
+    assert_eq!(tones(44100, 19000).1, SourceQuality::Lossy(256));
+    assert_eq!(tones(44100, 22000).1, SourceQuality::Lossless);
+    assert_eq!(tones(96000, 22000).1, SourceQuality::Upsampled);
+    assert_eq!(SourceQuality::Lossy(128).to_string(), "lossy-128k");
+
+    let silence = Spectrum::from_samples(std::iter::repeat_n(0.0, 8192), 44100);
+    assert_eq!(silence.source_quality(), SourceQuality::Lossless);
+}
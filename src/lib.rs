@@ -1,3 +1,8 @@
 pub mod commands;
 pub mod fingerprinting;
 pub mod music;
+pub mod server;
+pub mod tui;
+
+#[cfg(test)]
+pub mod test_helpers;
@@ -51,7 +51,7 @@ impl super::vertex::Vertex for Album {
             });
         };
         if let Some(albums) = cache.albums.get_mut(&self.artist_gel) {
-            albums.insert(self.name.to_string(), album_id);
+            albums.insert(self.name.clone(), album_id);
         }
         Ok(album_id)
     }
@@ -1,3 +1,5 @@
+use gel_derive::Queryable;
+use serde::Serialize;
 use std::collections::HashMap;
 
 use super::{cache::UpsertCache, config::Config, errors::CriticalErrorKind};
@@ -17,6 +19,19 @@ select Artist {
 order by .name
 ";
 
+#[derive(Queryable, Serialize)]
+pub struct ArtistOutput {
+    pub name: String,
+    pub rating: f64,
+    pub length: i64,
+    pub duration: String,
+    pub size: i64,
+    pub all_keywords: Vec<String>,
+    pub all_genres: Vec<String>,
+    pub n_albums: i64,
+    pub n_musics: i64,
+}
+
 pub struct Artist {
     pub name: String,
 }
@@ -1,11 +1,15 @@
 use super::errors::CriticalErrorKind;
 
+#[derive(Clone)]
 pub struct Config {
     pub dsn: String,
     pub gel: gel_tokio::Client,
     pub dry: bool,
     pub no_gel: bool,
     pub retries: u16,
+    pub datastore: String,
+    pub fingerprints: String,
+    pub ffmpeg: Option<String>,
 }
 
 impl Config {
@@ -21,6 +25,9 @@ impl Config {
             gel,
             no_gel,
             retries: 0,
+            datastore: String::new(),
+            fingerprints: String::new(),
+            ffmpeg: None,
         })
     }
 }
@@ -0,0 +1,50 @@
+use tabled::Table;
+
+use super::config::Config;
+use super::errors::CriticalErrorKind;
+use super::fingerprints::FingerprintIndex;
+use super::helpers::ReportOutput;
+
+const DEFAULT_MIN_SCORE: f64 = 0.1;
+
+#[derive(clap::ValueEnum, Clone, Default)]
+pub enum DuplicateCriteria {
+    /// Same recording, matched offline from indexed audio fingerprints
+    #[default]
+    Audio,
+}
+
+#[derive(clap::Parser)]
+#[clap(about = "Find duplicated musics")]
+pub struct Duplicates {
+    /// How musics are compared
+    #[clap(long, value_enum, default_value_t)]
+    by: DuplicateCriteria,
+
+    /// Minimum share of fingerprint landmarks matching at a same offset
+    #[clap(long, default_value_t = DEFAULT_MIN_SCORE)]
+    min_score: f64,
+
+    /// Report format
+    #[clap(long, value_enum, default_value_t)]
+    output: ReportOutput,
+}
+
+impl Duplicates {
+    pub fn duplicates(&self, config: &Config) -> Result<(), CriticalErrorKind> {
+        let duplicates = match self.by {
+            DuplicateCriteria::Audio => {
+                let index = FingerprintIndex::load(&config.fingerprints)?;
+                if index.is_empty() {
+                    eprintln!("No fingerprint indexed, run local fingerprint first");
+                }
+                index.duplicates(self.min_score)
+            }
+        };
+        match self.output {
+            ReportOutput::Table => println!("{}", Table::new(&duplicates)),
+            ReportOutput::Json => println!("{}", serde_json::to_string_pretty(&duplicates)?),
+        }
+        Ok(())
+    }
+}
@@ -37,28 +37,49 @@ pub enum CriticalErrorKind {
     Mp3TagError(#[from] id3::Error),
     #[error("Invalid Flac tag")]
     FlacTagError(#[from] metaflac::Error),
+    #[error("FLAC decoding error: {0}")]
+    FlacDecodeError(#[from] claxon::Error),
+    #[error("Loudness measure error: {0}")]
+    LoudnessError(#[from] ebur128::Error),
     #[error("Invalid Flac comments")]
     FlacCommentsError,
     #[error("Invalid progress bar template")]
     ProgressBarError(#[from] indicatif::style::TemplateError),
     #[error("Semaphore error")]
     SemaphoreError(#[from] tokio::sync::AcquireError),
+    #[error("Task error")]
+    TaskError(#[from] tokio::task::JoinError),
     #[error("JSON serialization error")]
     SerializationError(#[from] serde_json::Error),
     #[error("Relative path error")]
     RelativePathError(#[from] std::path::StripPrefixError),
     #[error("IndraDB datastore error")]
     DatastoreError(#[from] rmp_serde::decode::Error),
+    #[error("Datastore write error")]
+    DatastoreWriteError(#[from] rmp_serde::encode::Error),
     #[error("Music file size too large")]
     FileSizeError(#[from] std::num::TryFromIntError),
-    #[error("Music song not matched")]
+    #[error("No song matched for {path}")]
     NoMatch { path: String },
+    #[error("All {responses} responses replayed from {path}")]
+    ReplayExhausted { path: String, responses: usize },
+    #[error("Invalid Shazam response for {path}: {error}")]
+    ShazamResponseError {
+        path: String,
+        error: serde_json::Error,
+    },
     #[error("Time error")]
     TimeError(#[from] std::time::SystemTimeError),
     #[error("Base64 encode/decore error")]
     Base64Error(#[from] DecodeError),
     #[error("Rodio decoder error")]
     DecoderError(#[from] rodio::decoder::DecoderError),
+    #[error("Audio output error: {0}")]
+    AudioStreamError(#[from] rodio::StreamError),
+    #[error("Audio playback error: {0}")]
+    AudioPlayError(#[from] rodio::PlayError),
+    #[error("Seek error: {0}")]
+    SeekError(#[from] rodio::source::SeekError),
     #[error("Invalid sample rate")]
     InvalidSampleRate(u32),
     #[error("Invalid frequency band")]
@@ -75,4 +96,29 @@ pub enum CriticalErrorKind {
     InvalidCRC32(u32),
     #[error("Invalid URI")]
     InvalidURI(String),
+    #[error("Invalid URL: {0}")]
+    UrlError(#[from] url::ParseError),
+    #[error("Invalid base URL: {0}")]
+    InvalidBaseUrl(String),
+    #[error("--relative-to-out requires an output playlist path with --out")]
+    RelativeToOutWithoutOut,
+    #[error("MPD error: {0}")]
+    MpdError(String),
+    #[error("Unsupported music format: {0}")]
+    UnsupportedFormat(String),
+    #[error("FFMpeg not found")]
+    FfmpegNotFound,
+    #[error("Transcoding of {path} failed: {error}")]
+    TranscodeError { path: String, error: String },
+    #[error("FFMpeg decoding of {path} failed: {error}")]
+    FfmpegDecodeError { path: String, error: String },
+    #[error("Invalid filter: {0}")]
+    InvalidFilter(String),
+    #[error("Invalid template: {0}")]
+    InvalidTemplate(String),
+    #[error("Invalid placeholder {placeholder} in template: {template}")]
+    InvalidPlaceholder {
+        template: String,
+        placeholder: String,
+    },
 }
@@ -0,0 +1,335 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::errors::CriticalErrorKind;
+use super::helpers::{is_hidden, sanitize_file_name};
+use super::links::render_template;
+use super::music_result::MusicResult;
+use super::playlist::{Playlist, PlaylistOptions};
+#[cfg(feature = "ffmpeg")]
+use super::transcode::{Transcode, default_transcode_cache, validate_transcode};
+
+const DEFAULT_LAYOUT: &str = "{artist}/{album}/{track:02} - {title}.{ext}";
+
+/// FAT filesystems only store modification times with a 2 seconds precision
+const MTIME_TOLERANCE_SECS: u64 = 2;
+
+pub const EXPORT_PLACEHOLDERS: &[&str] = &[
+    "artist", "album", "title", "genre", "track", "rating", "ext",
+];
+
+#[derive(clap::Parser, Clone)]
+#[clap(about = "Export playlist tracks to a device or directory")]
+pub struct Export {
+    /// Destination directory
+    #[clap(long)]
+    dest: String,
+
+    /// Layout of exported files, relative to destination
+    #[clap(long, default_value_t = DEFAULT_LAYOUT.to_string(), value_parser = validate_layout)]
+    layout: String,
+
+    /// Delete files from destination which are not part of the playlist
+    #[clap(long)]
+    delete: bool,
+
+    /// Transcode lossless files, CODEC:BITRATE with mp3 or opus codecs
+    #[cfg(feature = "ffmpeg")]
+    #[clap(long, value_parser = validate_transcode)]
+    transcode: Option<Transcode>,
+
+    /// Transcoded files cache directory
+    #[cfg(feature = "ffmpeg")]
+    #[clap(long, default_value_t = default_transcode_cache())]
+    transcode_cache: String,
+}
+
+struct ExportedMusic<'a> {
+    source: &'a Path,
+    target: PathBuf,
+}
+
+impl Export {
+    pub fn export(
+        &self,
+        playlist: &Playlist,
+        playlist_options: &PlaylistOptions,
+        dry: bool,
+        ffmpeg: Option<&str>,
+    ) -> Result<(), CriticalErrorKind> {
+        let dest = Path::new(&self.dest);
+        let musics = playlist.ordered_musics(playlist_options)?;
+
+        let mut exported_musics = Vec::new();
+        let mut targets = HashSet::new();
+        for music in &musics {
+            let Some(source) = local_source(music) else {
+                eprintln!("{music} : no local file found, skipping");
+                continue;
+            };
+            let target = PathBuf::from(self.target(music, &self.extension(source))?);
+            if !targets.insert(target.clone()) {
+                eprintln!(
+                    "{} : {} already exported, skipping",
+                    source.display(),
+                    target.display()
+                );
+                continue;
+            }
+            exported_musics.push(ExportedMusic { source, target });
+        }
+
+        let export_bar = indicatif::ProgressBar::new(exported_musics.len() as u64);
+        export_bar.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template(
+                    "[{elapsed_precise}] Exporting files: {bar:50.cyan/blue} {pos:>7}/{len:7} {msg}",
+                )?
+                .progress_chars("##-"),
+        );
+
+        for exported_music in &exported_musics {
+            scopeguard::defer! {export_bar.inc(1)};
+            let target = dest.join(&exported_music.target);
+            let Some(source) = self.source(exported_music.source, dry, ffmpeg)? else {
+                export_bar.println(format!(
+                    "Transcode {} to {}",
+                    exported_music.source.display(),
+                    target.display()
+                ));
+                continue;
+            };
+            if is_up_to_date(&source, &target)? {
+                continue;
+            }
+            if dry {
+                export_bar.println(format!("Copy {} to {}", source.display(), target.display()));
+                continue;
+            }
+            copy_with_mtime(&source, &target)?;
+        }
+        export_bar.finish();
+
+        let playlist_file = PathBuf::from(format!("{}.m3u", sanitize_file_name(playlist.name())));
+        targets.insert(playlist_file.clone());
+
+        if self.delete {
+            self.delete_extras(&targets, dry)?;
+        }
+
+        let mut m3u = "#EXTM3U\n".to_string();
+        writeln!(m3u, "#EXTREM:name={}", playlist.name())?;
+        for exported_music in &exported_musics {
+            writeln!(m3u, "{}", exported_music.target.display())?;
+        }
+        let playlist_path = dest.join(playlist_file);
+        if dry {
+            eprintln!("Write playlist {}", playlist_path.display());
+        } else {
+            std::fs::create_dir_all(dest)?;
+            std::fs::write(playlist_path, m3u)?;
+        }
+        Ok(())
+    }
+
+    /// Extension of the exported file, which changes when transcoded
+    #[cfg_attr(not(feature = "ffmpeg"), allow(clippy::unused_self))]
+    fn extension(&self, source: &Path) -> String {
+        #[cfg(feature = "ffmpeg")]
+        if let Some(transcode) = &self.transcode
+            && transcode.applies(source)
+        {
+            return transcode.codec().extension().to_string();
+        }
+        source
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+
+    /// File to copy to the destination, the transcoded one from cache if asked,
+    /// none in dry mode when it is not transcoded yet
+    #[cfg_attr(
+        not(feature = "ffmpeg"),
+        allow(clippy::unused_self, clippy::unnecessary_wraps)
+    )]
+    fn source(
+        &self,
+        source: &Path,
+        dry: bool,
+        ffmpeg: Option<&str>,
+    ) -> Result<Option<PathBuf>, CriticalErrorKind> {
+        #[cfg(feature = "ffmpeg")]
+        if let Some(transcode) = &self.transcode
+            && transcode.applies(source)
+        {
+            let cache_dir = Path::new(&self.transcode_cache);
+            if dry {
+                let cached_path = transcode.cached_path(cache_dir, source)?;
+                return Ok(cached_path.is_file().then_some(cached_path));
+            }
+            return Ok(Some(transcode.transcode(ffmpeg, cache_dir, source)?));
+        }
+        #[cfg(not(feature = "ffmpeg"))]
+        let _ = (dry, ffmpeg);
+        Ok(Some(source.to_path_buf()))
+    }
+
+    fn target(&self, music: &MusicResult, ext: &str) -> Result<String, CriticalErrorKind> {
+        render_template(&self.layout, |key| {
+            let value = match key {
+                "artist" => music.artist_name.clone(),
+                "album" => music.album_name.clone(),
+                "title" => music.name.clone(),
+                "genre" => music.genre_name.clone(),
+                "track" => music.track.to_string(),
+                "rating" => music.rating.to_string(),
+                "ext" => ext.to_string(),
+                _ => return None,
+            };
+            Some(sanitize_file_name(&value))
+        })
+    }
+
+    fn delete_extras(
+        &self,
+        targets: &HashSet<PathBuf>,
+        dry: bool,
+    ) -> Result<(), CriticalErrorKind> {
+        let dest = Path::new(&self.dest);
+        if !dest.exists() {
+            return Ok(());
+        }
+        for entry in walkdir::WalkDir::new(dest)
+            .contents_first(true)
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !is_hidden(e))
+            .filter_map(std::result::Result::ok)
+        {
+            let relative = entry.path().strip_prefix(dest)?;
+            if entry.file_type().is_file() && !targets.contains(relative) {
+                if dry {
+                    eprintln!("Delete {}", entry.path().display());
+                } else {
+                    std::fs::remove_file(entry.path())?;
+                }
+            } else if !dry
+                && entry.depth() > 0
+                && entry.file_type().is_dir()
+                && std::fs::read_dir(entry.path())?.next().is_none()
+            {
+                std::fs::remove_dir(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// First path of the music available on this machine
+#[must_use]
+pub fn local_source(music: &MusicResult) -> Option<&Path> {
+    music
+        .folders
+        .iter()
+        .map(|folder| Path::new(&folder.path))
+        .find(|path| path.is_file())
+}
+
+fn is_up_to_date(source: &Path, target: &Path) -> Result<bool, CriticalErrorKind> {
+    let Ok(target_metadata) = std::fs::metadata(target) else {
+        return Ok(false);
+    };
+    let source_metadata = std::fs::metadata(source)?;
+    if source_metadata.len() != target_metadata.len() {
+        return Ok(false);
+    }
+    Ok(
+        mtime_secs(source_metadata.modified()?)?.abs_diff(mtime_secs(target_metadata.modified()?)?)
+            <= MTIME_TOLERANCE_SECS,
+    )
+}
+
+pub fn mtime_secs(mtime: SystemTime) -> Result<u64, CriticalErrorKind> {
+    Ok(mtime.duration_since(SystemTime::UNIX_EPOCH)?.as_secs())
+}
+
+/// Copy a file, creating parent directories, and keep its modification time
+pub fn copy_with_mtime(source: &Path, target: &Path) -> Result<(), CriticalErrorKind> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(source, target)?;
+    let mtime = std::fs::metadata(source)?.modified()?;
+    std::fs::File::options()
+        .write(true)
+        .open(target)?
+        .set_modified(mtime)?;
+    Ok(())
+}
+
+pub fn validate_layout(layout: &str) -> Result<String, String> {
+    if let Err(e) = render_template(layout, |key| {
+        EXPORT_PLACEHOLDERS.contains(&key).then(String::new)
+    }) {
+        return Err(format!(
+            "{layout} : {e}, valid placeholders: {}",
+            EXPORT_PLACEHOLDERS.join(", ")
+        ));
+    }
+    if Path::new(layout).is_absolute() {
+        return Err(format!("{layout} : layout should be relative"));
+    }
+    Ok(layout.to_string())
+}
+
+#[test]
+fn export_tests() {
+    let source_dir = tempfile::tempdir().unwrap();
+    let dest_dir = tempfile::tempdir().unwrap();
+    let source = source_dir.path().join("a.flac");
+    std::fs::write(&source, b"flac").unwrap();
+    std::fs::create_dir_all(dest_dir.path().join("Old")).unwrap();
+    std::fs::write(dest_dir.path().join("Old/b.mp3"), b"mp3").unwrap();
+
+    let music = MusicResult {
+        artist_name: "AC/DC".to_string(),
+        album_name: String::new(),
+        size: 4,
+        track: 3,
+        rating: 4.5,
+        ..crate::test_helpers::music(
+            "Song: 1",
+            &source_dir.path().display().to_string(),
+            &source.display().to_string(),
+        )
+    };
+    let export = Export {
+        dest: dest_dir.path().display().to_string(),
+        layout: DEFAULT_LAYOUT.to_string(),
+        delete: true,
+        #[cfg(feature = "ffmpeg")]
+        transcode: None,
+        #[cfg(feature = "ffmpeg")]
+        transcode_cache: default_transcode_cache(),
+    };
+    let playlist = Playlist::new("best", &[music]);
+    let target = dest_dir.path().join("AC_DC/Unknown/03 - Song_ 1.flac");
+
+    export
+        .export(&playlist, &PlaylistOptions::default(), true, None)
+        .unwrap();
+    assert!(!target.exists());
+
+    export
+        .export(&playlist, &PlaylistOptions::default(), false, None)
+        .unwrap();
+    assert_eq!(std::fs::read(&target).unwrap(), b"flac");
+    assert!(is_up_to_date(&source, &target).unwrap());
+    assert!(!dest_dir.path().join("Old").exists());
+    assert_eq!(
+        std::fs::read_to_string(dest_dir.path().join("best.m3u")).unwrap(),
+        "#EXTM3U\n#EXTREM:name=best\nAC_DC/Unknown/03 - Song_ 1.flac\n"
+    );
+}
@@ -29,6 +29,8 @@ const fn default_limit() -> i64 {
 const MATCH_ALL: &str = "(.*?)";
 const EMPTY_STRING_REGEX: &str = "^$";
 const DEFAULT_PATTERN: &str = "";
+const BROKEN_REGEX: &str = "^broken$";
+const SUSPICIOUS_LOSSLESS_REGEX: &str = "^(lossy|upsampled)";
 const NO_KEYWORD: &str = "^((?!cutoff|bad|demo|intro).)$";
 
 fn default_match_all() -> String {
@@ -89,6 +91,16 @@ pub struct Filter {
     #[clap(long, default_value_t = default_pattern())]
     pub pattern: String,
 
+    /// Integrity stored by local verify, ok or broken, empty when never verified
+    #[serde(default = "default_match_all")]
+    #[clap(long, default_value_t = default_match_all())]
+    pub integrity: String,
+
+    /// Source quality stored by local lossless, lossless, lossy-<bitrate>k or upsampled
+    #[serde(default = "default_match_all")]
+    #[clap(long, default_value_t = default_match_all())]
+    pub spectrum: String,
+
     #[serde(default = "default_limit")]
     #[clap(long, default_value_t = default_limit())]
     pub limit: i64,
@@ -129,16 +141,18 @@ impl Default for Filter {
             title: default_match_all(),
             keyword: default_match_all(),
             pattern: default_pattern(),
+            integrity: default_match_all(),
+            spectrum: default_match_all(),
             limit: default_limit(),
         }
     }
 }
 
 fn validate_rating(rating_str: &str) -> Result<f64, String> {
-    if let Ok(rating) = rating_str.parse::<f64>() {
-        if RATINGS.contains(&rating) {
-            return Ok(rating);
-        }
+    if let Ok(rating) = rating_str.parse::<f64>()
+        && RATINGS.contains(&rating)
+    {
+        return Ok(rating);
     }
     Err(format!(
         "{rating_str} is invalid rating, valid values: {}",
@@ -225,6 +239,22 @@ pub static DEFAULT_FILTERS: std::sync::LazyLock<std::collections::HashMap<String
             },
         );
 
+        filters.insert(
+            "broken".to_string(),
+            Filter {
+                integrity: BROKEN_REGEX.to_string(),
+                ..Filter::default()
+            },
+        );
+
+        filters.insert(
+            "suspicious-lossless".to_string(),
+            Filter {
+                spectrum: SUSPICIOUS_LOSSLESS_REGEX.to_string(),
+                ..Filter::default()
+            },
+        );
+
         filters.insert(
             "best-4.0".to_string(),
             Filter {
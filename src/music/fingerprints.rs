@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use super::config::Config;
+use super::errors::CriticalErrorKind;
+use super::export::mtime_secs;
+use super::filter::Filters;
+use super::folders::local_files;
+use crate::fingerprinting::algorithm::{SAMPLE_RATE, SignatureGenerator};
+use crate::fingerprinting::landmarks::{Landmark, landmarks};
+
+/// Samples between two FFT passes of the signature generator
+const SAMPLES_PER_PASS: f64 = 128.0;
+/// Landmarks shared by too many tracks are silence or noise, and are not matched
+const MAX_BUCKET_LEN: usize = 256;
+
+#[derive(Serialize, Deserialize)]
+pub struct IndexedTrack {
+    pub modified: u64,
+    pub number_samples: u32,
+    pub landmarks: Vec<Landmark>,
+}
+
+impl IndexedTrack {
+    pub fn from_file(path: &str, ffmpeg: Option<&str>) -> Result<Self, CriticalErrorKind> {
+        let modified = mtime_secs(std::fs::metadata(path)?.modified()?)?;
+        let samples = SignatureGenerator::decode_file(path, ffmpeg)?;
+        Ok(Self::from_buffer(modified, &samples))
+    }
+
+    #[must_use]
+    pub fn from_buffer(modified: u64, samples: &[i16]) -> Self {
+        let signature = SignatureGenerator::make_signature_from_buffer(samples);
+        Self {
+            modified,
+            number_samples: signature.number_samples,
+            landmarks: landmarks(&signature),
+        }
+    }
+}
+
+/// Tracks sharing landmarks at a constant time offset
+#[derive(Serialize, tabled::Tabled, Debug)]
+pub struct AudioMatch {
+    pub path: String,
+    pub duplicate: String,
+    #[tabled(display("display_score"))]
+    pub score: f64,
+    pub matches: usize,
+    /// Seconds to add to a time of the first track to get the same audio in the duplicate
+    #[tabled(display("display_offset"))]
+    pub offset: f64,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn display_score(score: &f64) -> String {
+    format!("{:.0}%", score * 100.0)
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn display_offset(offset: &f64) -> String {
+    format!("{offset:+.2}s")
+}
+
+/// Landmarks of local tracks, stored in their own index file
+#[derive(Serialize, Deserialize, Default)]
+pub struct FingerprintIndex {
+    tracks: BTreeMap<String, IndexedTrack>,
+}
+
+impl FingerprintIndex {
+    pub fn load(path: &str) -> Result<Self, CriticalErrorKind> {
+        match std::fs::read(path) {
+            Ok(data) => Ok(rmp_serde::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write to a temporary file first, an interrupted save keeping the previous index
+    pub fn save(&self, path: &str) -> Result<(), CriticalErrorKind> {
+        let temporary = format!("{path}.tmp");
+        std::fs::write(&temporary, rmp_serde::to_vec(self)?)?;
+        std::fs::rename(temporary, path)?;
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    pub fn insert(&mut self, path: &str, track: IndexedTrack) {
+        self.tracks.insert(path.to_string(), track);
+    }
+
+    /// Whether a file is indexed with its current modification time
+    #[must_use]
+    pub fn is_up_to_date(&self, path: &str) -> bool {
+        self.tracks.get(path).is_some_and(|track| {
+            std::fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| mtime_secs(modified).ok())
+                == Some(track.modified)
+        })
+    }
+
+    /// Remove tracks whose file was deleted, returning how many
+    pub fn prune(&mut self) -> usize {
+        let len = self.tracks.len();
+        self.tracks.retain(|path, _| Path::new(path).is_file());
+        len - self.tracks.len()
+    }
+
+    /// Pairs of tracks sharing at least `min_score` of their landmarks at a same time offset
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn duplicates(&self, min_score: f64) -> Vec<AudioMatch> {
+        let tracks = self.tracks.iter().collect::<Vec<_>>();
+        let mut buckets: HashMap<u32, Vec<(usize, u32)>> = HashMap::new();
+        for (index, (_, track)) in tracks.iter().enumerate() {
+            for (hash, pass) in &track.landmarks {
+                buckets.entry(*hash).or_default().push((index, *pass));
+            }
+        }
+
+        let mut duplicates = Vec::new();
+        for (index, (path, track)) in tracks.iter().enumerate() {
+            let mut offsets: HashMap<(usize, i64), usize> = HashMap::new();
+            for (hash, pass) in &track.landmarks {
+                let Some(bucket) = buckets.get(hash) else {
+                    continue;
+                };
+                if bucket.len() > MAX_BUCKET_LEN {
+                    continue;
+                }
+                for (other, other_pass) in bucket {
+                    if *other > index {
+                        *offsets
+                            .entry((*other, i64::from(*other_pass) - i64::from(*pass)))
+                            .or_default() += 1;
+                    }
+                }
+            }
+
+            let mut best: HashMap<usize, (usize, i64)> = HashMap::new();
+            for ((other, offset), matches) in offsets {
+                let entry = best.entry(other).or_insert((matches, offset));
+                if matches > entry.0 {
+                    *entry = (matches, offset);
+                }
+            }
+            for (other, (matches, offset)) in best {
+                let (other_path, other_track) = tracks[other];
+                let landmarks_len = track.landmarks.len().min(other_track.landmarks.len());
+                if landmarks_len == 0 {
+                    continue;
+                }
+                let score = matches as f64 / landmarks_len as f64;
+                if score >= min_score {
+                    duplicates.push(AudioMatch {
+                        path: (*path).clone(),
+                        duplicate: other_path.clone(),
+                        score: score.min(1.0),
+                        matches,
+                        offset: offset as f64 * SAMPLES_PER_PASS / SAMPLE_RATE as f64,
+                    });
+                }
+            }
+        }
+        duplicates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        duplicates
+    }
+}
+
+#[derive(clap::Parser)]
+#[clap(about = "Index audio fingerprints of musics, for offline matching")]
+pub struct Fingerprint {
+    /// Fingerprint again files already indexed
+    #[clap(long)]
+    force: bool,
+
+    /// Filters selecting musics, when no folder is given
+    #[clap(flatten)]
+    filters: Filters,
+
+    /// Folders to fingerprint instead of filtered musics
+    folders: Vec<String>,
+}
+
+impl Fingerprint {
+    pub async fn fingerprint(&self, config: Config) -> Result<(), CriticalErrorKind> {
+        let mut index = FingerprintIndex::load(&config.fingerprints)?;
+        let pruned = index.prune();
+        let files = Box::pin(local_files(&config, &self.folders, &self.filters))
+            .await?
+            .into_iter()
+            .map(|(_, path)| path)
+            .filter(|path| self.force || !index.is_up_to_date(path))
+            .collect::<Vec<_>>();
+
+        let fingerprint_bar = indicatif::ProgressBar::new(files.len() as u64);
+        fingerprint_bar.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template(
+                    "[{elapsed_precise}] Fingerprinting files: {bar:50.cyan/blue} {pos:>7}/{len:7} {msg}",
+                )?
+                .progress_chars("##-"),
+        );
+        for path in files {
+            scopeguard::defer! {fingerprint_bar.inc(1)};
+            match IndexedTrack::from_file(&path, config.ffmpeg.as_deref()) {
+                Ok(track) => index.insert(&path, track),
+                Err(e) => fingerprint_bar.println(format!("{path} : {e}")),
+            }
+        }
+        fingerprint_bar.finish();
+
+        eprintln!("{} tracks indexed, {pruned} removed", index.len());
+        if !config.dry {
+            index.save(&config.fingerprints)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+#[allow(clippy::cast_precision_loss)]
+fn fingerprint_index_tests() {
+    // Tones changing every quarter second, seeded to be reproducible
+    let tones = |seed: u64, seconds: usize| -> Vec<i16> {
+        let mut state = seed;
+        let mut samples = Vec::with_capacity(seconds * SAMPLE_RATE);
+        for _ in 0..seconds * 4 {
+            let state = crate::test_helpers::lcg(&mut state);
+            let frequencies = [300 + (state >> 33) % 3000, 300 + (state >> 45) % 3000];
+            for n in 0..SAMPLE_RATE / 4 {
+                let t = n as f64 / SAMPLE_RATE as f64;
+                let sample = frequencies
+                    .iter()
+                    .map(|f| (2.0 * std::f64::consts::PI * *f as f64 * t).sin())
+                    .sum::<f64>();
+                #[allow(clippy::cast_possible_truncation)]
+                samples.push((sample * 8000.0) as i16);
+            }
+        }
+        samples
+    };
+
+    let original = tones(1, 20);
+    let mut shifted = vec![0; 2 * SAMPLE_RATE];
+    shifted.extend(original.iter().map(|sample| sample / 2));
+
+    let mut index = FingerprintIndex::default();
+    index.insert("a.flac", IndexedTrack::from_buffer(0, &original));
+    index.insert("b.mp3", IndexedTrack::from_buffer(0, &shifted));
+    index.insert("c.flac", IndexedTrack::from_buffer(0, &tones(2, 20)));
+
+    let duplicates = index.duplicates(0.1);
+    assert_eq!(duplicates.len(), 1, "{duplicates:?}");
+    assert_eq!(duplicates[0].path, "a.flac");
+    assert_eq!(duplicates[0].duplicate, "b.mp3");
+    assert!((duplicates[0].offset - 2.0).abs() < 0.1);
+
+    let fingerprints = tempfile::NamedTempFile::new().unwrap();
+    let fingerprints = fingerprints.path().to_str().unwrap();
+    index.save(fingerprints).unwrap();
+    let index = FingerprintIndex::load(fingerprints).unwrap();
+    assert_eq!(index.len(), 3);
+    assert_eq!(index.duplicates(0.1).len(), 1);
+}
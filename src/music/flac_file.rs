@@ -3,9 +3,13 @@ use metaflac::block::VorbisComment;
 use num_traits::ToPrimitive;
 
 use super::errors::CriticalErrorKind;
-use super::music_file::MusicFile;
+use super::loudness::ReplayGain;
+use super::music_file::{MusicFile, MusicFileMut};
 use super::ratings::Rating;
 
+const FMPS_RATING: &str = "fmps_rating";
+const DESCRIPTION: &str = "description";
+
 pub struct FlacFile {
     folder: String,
     path: String,
@@ -28,6 +32,46 @@ impl FlacFile {
     }
 }
 
+impl MusicFileMut for FlacFile {
+    fn set_rating(&mut self, rating: Rating) {
+        let fmps_rating: f64 = rating.into();
+        self.tag
+            .set_vorbis(FMPS_RATING, vec![(fmps_rating / 5.0).to_string()]);
+    }
+
+    fn set_keywords(&mut self, keywords: &[String]) {
+        self.tag.set_vorbis(DESCRIPTION, vec![keywords.join(" ")]);
+    }
+
+    fn set_artist(&mut self, artist: &str) {
+        self.tag.vorbis_comments_mut().set_artist(vec![artist]);
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.tag.vorbis_comments_mut().set_title(vec![title]);
+    }
+
+    fn set_album(&mut self, album: &str) {
+        self.tag.vorbis_comments_mut().set_album(vec![album]);
+    }
+
+    fn set_replay_gain(&mut self, replay_gain: &ReplayGain) {
+        for (key, value) in replay_gain.tags() {
+            // Also drop the lower case comment written by some scanners
+            self.tag.remove_vorbis(&key.to_lowercase());
+            self.tag.set_vorbis(key, vec![value]);
+        }
+    }
+
+    fn save(&mut self) -> Result<(), CriticalErrorKind> {
+        self.tag.save()?;
+        if let Some(comments) = self.tag.vorbis_comments() {
+            self.comments = comments.clone();
+        }
+        Ok(())
+    }
+}
+
 impl MusicFile for FlacFile {
     fn path(&self) -> &str {
         &self.path
@@ -52,52 +96,52 @@ impl MusicFile for FlacFile {
     }
 
     fn artist(&self) -> &str {
-        if let Some(artists) = self.comments.artist() {
-            if !artists.is_empty() {
-                return artists[0].as_str();
-            }
+        if let Some(artists) = self.comments.artist()
+            && !artists.is_empty()
+        {
+            return artists[0].as_str();
         }
         ""
     }
 
     fn title(&self) -> &str {
-        if let Some(titles) = self.comments.title() {
-            if !titles.is_empty() {
-                return titles[0].as_str();
-            }
+        if let Some(titles) = self.comments.title()
+            && !titles.is_empty()
+        {
+            return titles[0].as_str();
         }
         ""
     }
 
     fn album(&self) -> &str {
-        if let Some(albums) = self.comments.album() {
-            if !albums.is_empty() {
-                return albums[0].as_str();
-            }
+        if let Some(albums) = self.comments.album()
+            && !albums.is_empty()
+        {
+            return albums[0].as_str();
         }
         ""
     }
 
     fn genre(&self) -> &str {
-        if let Some(genres) = self.comments.genre() {
-            if !genres.is_empty() {
-                return genres[0].as_str();
-            }
+        if let Some(genres) = self.comments.genre()
+            && !genres.is_empty()
+        {
+            return genres[0].as_str();
         }
         ""
     }
 
     fn track(&self) -> i64 {
-        if let Some(track) = self.comments.track() {
-            if let Ok(track) = track.to_string().parse::<i64>() {
-                return track;
-            }
+        if let Some(track) = self.comments.track()
+            && let Ok(track) = track.to_string().parse::<i64>()
+        {
+            return track;
         }
         0
     }
 
     fn rating(&self) -> Result<Rating, CriticalErrorKind> {
-        if let Some(fmps_ratings) = self.tag.get_vorbis("fmps_rating") {
+        if let Some(fmps_ratings) = self.tag.get_vorbis(FMPS_RATING) {
             for fmps_rating in fmps_ratings {
                 if let Ok(mut rating) = fmps_rating.to_string().parse::<f64>() {
                     rating *= 5.0;
@@ -110,13 +154,13 @@ impl MusicFile for FlacFile {
     }
 
     fn keywords(&self) -> Vec<String> {
-        if let Some(descriptions) = self.tag.get_vorbis("description") {
-            if let Some(description) = descriptions.into_iter().next() {
-                return description
-                    .split_whitespace()
-                    .map(|k| k.trim_matches(char::from(0)).to_string())
-                    .collect();
-            }
+        if let Some(descriptions) = self.tag.get_vorbis(DESCRIPTION)
+            && let Some(description) = descriptions.into_iter().next()
+        {
+            return description
+                .split_whitespace()
+                .map(|k| k.trim_matches(char::from(0)).to_string())
+                .collect();
         }
         Vec::new()
     }
@@ -1,6 +1,13 @@
 use super::errors::CriticalErrorKind;
+use super::filter::Filters;
+use super::helpers::is_hidden;
+use super::music_result::MusicResult;
+use super::playlist::{Playlist, PlaylistOptions};
 use super::{cache::UpsertCache, config::Config};
 use gel_derive::Queryable;
+use std::path::Path;
+
+const LOCAL_FILES_NAME: &str = "local";
 
 #[derive(clap::Parser)]
 #[clap(about = "List folders")]
@@ -115,3 +122,42 @@ select upsert_folder(
     username := <str>$1,
     ipv4 := <str>$2
 ).id";
+
+/// Local files of given folders, or of filtered musics, with their folder
+pub async fn local_files(
+    config: &Config,
+    folders: &[String],
+    filters: &Filters,
+) -> Result<Vec<(String, String)>, CriticalErrorKind> {
+    if folders.is_empty() {
+        let playlist = Box::pin(Playlist::from_filters(
+            &config.gel,
+            LOCAL_FILES_NAME,
+            filters,
+        ))
+        .await?;
+        return Ok(playlist
+            .ordered_musics(&PlaylistOptions::default())?
+            .iter()
+            .flat_map(MusicResult::local_folders)
+            .map(|folder| (folder.name.clone(), folder.path.clone()))
+            .collect());
+    }
+    let mut files = Vec::new();
+    for folder in folders {
+        files.extend(
+            walkdir::WalkDir::new(Path::new(folder))
+                .into_iter()
+                .filter_entry(|e| !is_hidden(e))
+                .filter_map(std::result::Result::ok)
+                .filter(|e| {
+                    e.file_type().is_file()
+                        && e.path()
+                            .extension()
+                            .is_some_and(|ext| ext == "flac" || ext == "mp3")
+                })
+                .map(|e| (folder.clone(), e.path().display().to_string())),
+        );
+    }
+    Ok(files)
+}
@@ -1,9 +1,20 @@
 use itertools::Itertools;
 use num_traits::ToPrimitive;
+use std::path::{Component, Path, PathBuf};
 use std::{collections::HashSet, hash::Hash, iter::zip};
 
 use super::errors::CriticalErrorKind;
 
+const UNKNOWN: &str = "Unknown";
+
+/// Format of the reports of verification commands
+#[derive(clap::ValueEnum, Clone, Default)]
+pub enum ReportOutput {
+    #[default]
+    Table,
+    Json,
+}
+
 pub fn has_unique_elements<T>(iter: T) -> bool
 where
     T: IntoIterator,
@@ -21,6 +32,61 @@ pub fn is_hidden(entry: &walkdir::DirEntry) -> bool {
         .is_some_and(|s| s.starts_with('.'))
 }
 
+/// Escape characters interpreted by a shell, used for SSH links
+#[must_use]
+pub fn shell_escape(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        if !(c.is_alphanumeric() || "/._-+,:@%".contains(c)) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Path leading to `path` from the `base` directory, both being absolute
+#[must_use]
+pub fn relative_path_from(path: &Path, base: &Path) -> PathBuf {
+    let path_components = path.components().collect::<Vec<Component>>();
+    let base_components = base
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect::<Vec<Component>>();
+    let common = zip(&path_components, &base_components)
+        .take_while(|(p, b)| p == b)
+        .count();
+    let mut relative = PathBuf::new();
+    for _ in common..base_components.len() {
+        relative.push(Component::ParentDir);
+    }
+    for component in &path_components[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+/// Replace characters forbidden in file names by common filesystems (FAT, NTFS, ext4)
+#[must_use]
+pub fn sanitize_file_name(name: &str) -> String {
+    let sanitized = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || "/\\:*?\"<>|".contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+    let sanitized = sanitized.trim().trim_end_matches('.');
+    if sanitized.is_empty() {
+        UNKNOWN.to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
 pub async fn public_ip() -> Result<String, CriticalErrorKind> {
     let client = reqwest::Client::new();
     let response = client.head("https://www.wikipedia.org").send().await?;
@@ -99,6 +165,36 @@ where
     Ok(elements)
 }
 
+/// Parse a duration like 90, 12s, 500ms, 1m30s or 1h
+pub fn parse_duration(duration: &str) -> Result<std::time::Duration, String> {
+    let error = || format!("{duration} is not a duration like 12s, 500ms or 1m30s");
+    if let Ok(seconds) = duration.parse::<u64>() {
+        return Ok(std::time::Duration::from_secs(seconds));
+    }
+    let mut total = std::time::Duration::ZERO;
+    let mut rest = duration;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or_else(error)?;
+        let value = rest[..digits].parse::<u64>().map_err(|_| error())?;
+        rest = &rest[digits..];
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        total += match &rest[..unit] {
+            "h" => std::time::Duration::from_secs(value * 3600),
+            "m" => std::time::Duration::from_secs(value * 60),
+            "s" => std::time::Duration::from_secs(value),
+            "ms" => std::time::Duration::from_millis(value),
+            _ => return Err(error()),
+        };
+        rest = &rest[unit..];
+    }
+    if total.is_zero() {
+        return Err(error());
+    }
+    Ok(total)
+}
+
 #[test]
 fn interleave_evenly_tests() {
     let iterables = vec![vec![1, 3, 5, 7], vec![0, 2, 4, 6]];
@@ -109,3 +205,34 @@ fn interleave_evenly_tests() {
     let result = interleave_evenly(iterables);
     assert_eq!(vec![0, 1, 11, 2, 3, 12], result.unwrap());
 }
+
+#[test]
+fn parse_duration_tests() {
+    use std::time::Duration;
+    assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("12s"), Ok(Duration::from_secs(12)));
+    assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+    assert_eq!(parse_duration("1m30s"), Ok(Duration::from_secs(90)));
+    assert!(parse_duration("").is_err());
+    assert!(parse_duration("12x").is_err());
+    assert!(parse_duration("s").is_err());
+}
+
+#[test]
+fn relative_path_from_tests() {
+    assert_eq!(
+        relative_path_from(
+            Path::new("/mnt/Music/Artist/a.flac"),
+            Path::new("/mnt/Music/Playlists")
+        ),
+        PathBuf::from("../Artist/a.flac")
+    );
+    assert_eq!(
+        relative_path_from(Path::new("/mnt/Music/a.flac"), Path::new("/mnt/Music")),
+        PathBuf::from("a.flac")
+    );
+    assert_eq!(
+        relative_path_from(Path::new("/data/a.flac"), Path::new("/mnt/Music/Playlists")),
+        PathBuf::from("../../../data/a.flac")
+    );
+}
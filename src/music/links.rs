@@ -0,0 +1,355 @@
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use super::errors::CriticalErrorKind;
+use super::helpers::{relative_path_from, shell_escape};
+use super::playlist::Kind;
+
+const HOST_PLACEHOLDER: &str = "{host}";
+const DEFAULT_HTTP_BASE: &str = "http://{host}";
+
+/// Same set as the one used by URL path segments
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'<')
+    .add(b'>')
+    .add(b'`')
+    .add(b'#')
+    .add(b'?')
+    .add(b'{')
+    .add(b'}')
+    .add(b'/')
+    .add(b'%');
+
+pub const LINK_PLACEHOLDERS: &[&str] = &[
+    "folder",
+    "folder_rel",
+    "path",
+    "username",
+    "ipv4",
+    "title",
+    "artist",
+    "album",
+    "genre",
+    "track",
+    "rating",
+    "length",
+    "size",
+];
+
+#[derive(clap::Parser, Default, Clone)]
+pub struct LinkOptions {
+    /// Link kinds to generate
+    #[clap(long, value_enum)]
+    kind: Vec<Kind>,
+
+    /// Strip the folder root from paths
+    #[clap(long)]
+    relative: bool,
+
+    /// Make paths relative to the directory of the output playlist, given with --out
+    #[clap(long, conflicts_with = "relative")]
+    relative_to_out: bool,
+
+    #[clap(skip)]
+    playlist_dir: Option<PathBuf>,
+
+    #[clap(skip)]
+    http_host: Option<String>,
+
+    /// HTTP base URL for links, [FOLDER=]URL, {host} being replaced by localhost or the folder IPv4
+    #[clap(long = "http-base", value_parser = validate_http_base)]
+    http_bases: Vec<HttpBase>,
+
+    /// Custom link, NAME=TEMPLATE with placeholders like {folder_rel}, {ipv4} or {artist}, suffixed by :url or :shell to escape them
+    #[clap(long = "link", value_parser = validate_link_template)]
+    link_templates: Vec<LinkTemplate>,
+}
+
+impl LinkOptions {
+    /// HTTP links of musics served by this host, reachable at an address like 192.168.0.1:8080
+    #[must_use]
+    pub fn served_by(http_host: &str) -> Self {
+        Self {
+            kind: vec![Kind::LocalHTTP],
+            http_host: Some(http_host.to_string()),
+            ..Self::default()
+        }
+    }
+    /// Host overriding localhost and folders IPv4 in HTTP links
+    #[must_use]
+    pub fn http_host(&self) -> Option<&str> {
+        self.http_host.as_deref()
+    }
+    /// Selected link kinds, local paths when nothing is asked
+    #[must_use]
+    pub fn kinds(&self) -> Vec<Kind> {
+        if self.kind.is_empty() && self.link_templates.is_empty() {
+            vec![Kind::Local]
+        } else {
+            self.kind.clone()
+        }
+    }
+    #[must_use]
+    pub fn relative(&self) -> bool {
+        self.relative
+    }
+    /// Resolve the playlist directory paths are made relative to, if asked
+    pub fn for_playlist(&self, out: Option<&str>) -> Result<Self, CriticalErrorKind> {
+        let mut link_options = self.clone();
+        if self.relative_to_out {
+            // Commands without --out flatten these options too, so clap cannot require it
+            let Some(out) = out else {
+                return Err(CriticalErrorKind::RelativeToOutWithoutOut);
+            };
+            let playlist_dir = match Path::new(out).parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            link_options.playlist_dir = Some(std::path::absolute(playlist_dir)?);
+        }
+        Ok(link_options)
+    }
+    /// Local path of a music, as seen from the playlist or the folder root
+    pub fn local_path(&self, folder: &str, path: &str) -> Result<String, CriticalErrorKind> {
+        let path = if let Some(playlist_dir) = &self.playlist_dir {
+            relative_path_from(&std::path::absolute(path)?, playlist_dir)
+        } else if self.relative {
+            Path::new(path).strip_prefix(folder)?.to_path_buf()
+        } else {
+            PathBuf::from(path)
+        };
+        Ok(path.display().to_string())
+    }
+    #[must_use]
+    pub fn http_bases(&self) -> &[HttpBase] {
+        &self.http_bases
+    }
+    #[must_use]
+    pub fn link_templates(&self) -> &[LinkTemplate] {
+        &self.link_templates
+    }
+}
+
+/// User defined link kind, rendered for each folder of a music
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkTemplate {
+    name: String,
+    template: String,
+}
+
+impl LinkTemplate {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn render<F>(&self, value: F) -> Result<String, CriticalErrorKind>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        render_template(&self.template, value)
+    }
+}
+
+/// Replace `{placeholder}` or `{placeholder:url}` / `{placeholder:shell}` by their escaped value,
+/// `{placeholder:02}` pads it to a width, `{{` and `}}` being literal braces
+pub fn render_template<F>(template: &str, value: F) -> Result<String, CriticalErrorKind>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut rendered = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                rendered.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                rendered.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => {
+                            return Err(CriticalErrorKind::InvalidTemplate(template.to_string()));
+                        }
+                    }
+                }
+                let (key, spec) = match placeholder.split_once(':') {
+                    Some((key, spec)) => (key, Some(spec)),
+                    None => (placeholder.as_str(), None),
+                };
+                let Some(value) = value(key) else {
+                    return Err(CriticalErrorKind::InvalidPlaceholder {
+                        template: template.to_string(),
+                        placeholder: key.to_string(),
+                    });
+                };
+                match (spec, spec.map(str::parse::<usize>)) {
+                    (None, _) => rendered.push_str(&value),
+                    (Some("url"), _) => rendered.push_str(&encode_path(&value)),
+                    (Some("shell"), _) => rendered.push_str(&shell_escape(&value)),
+                    (Some(spec), Some(Ok(width))) if spec.starts_with('0') => {
+                        write!(rendered, "{value:0>width$}")?;
+                    }
+                    (Some(_), Some(Ok(width))) => write!(rendered, "{value:>width$}")?,
+                    (Some(_), _) => {
+                        return Err(CriticalErrorKind::InvalidPlaceholder {
+                            template: template.to_string(),
+                            placeholder,
+                        });
+                    }
+                }
+            }
+            c => rendered.push(c),
+        }
+    }
+    Ok(rendered)
+}
+
+/// Percent-encode each segment of a path, keeping separators
+#[must_use]
+pub fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn file_uri(path: &str) -> Result<String, CriticalErrorKind> {
+    match Url::from_file_path(path) {
+        Ok(url) => Ok(url.to_string()),
+        Err(()) => Err(CriticalErrorKind::InvalidURI(path.to_string())),
+    }
+}
+
+pub fn validate_link_template(link_template: &str) -> Result<LinkTemplate, String> {
+    let Some((name, template)) = link_template.split_once('=') else {
+        return Err(format!("{link_template} should be NAME=TEMPLATE"));
+    };
+    let link_template = LinkTemplate {
+        name: name.trim().to_string(),
+        template: template.trim().to_string(),
+    };
+    if let Err(e) = link_template.render(|key| LINK_PLACEHOLDERS.contains(&key).then(String::new)) {
+        return Err(format!(
+            "{} : {e}, valid placeholders: {}",
+            link_template.name,
+            LINK_PLACEHOLDERS.join(", ")
+        ));
+    }
+    Ok(link_template)
+}
+
+/// Base URL template mapping a folder root (or every folder) to an HTTP location,
+/// `{host}` is replaced by localhost or the folder IPv4 depending on the link kind
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpBase {
+    folder: Option<String>,
+    template: String,
+}
+
+impl HttpBase {
+    #[must_use]
+    pub fn folder(&self) -> Option<&str> {
+        self.folder.as_deref()
+    }
+
+    pub fn url(&self, host: &str) -> Result<Url, CriticalErrorKind> {
+        base_url(&self.template, host)
+    }
+
+    /// Find the base URL configured for this folder, falling back on the global one
+    #[must_use]
+    pub fn find<'a>(http_bases: &'a [HttpBase], folder: &str) -> Option<&'a HttpBase> {
+        http_bases
+            .iter()
+            .find(|base| {
+                base.folder()
+                    .is_some_and(|f| Path::new(f) == Path::new(folder))
+            })
+            .or_else(|| http_bases.iter().find(|base| base.folder.is_none()))
+    }
+}
+
+fn base_url(template: &str, host: &str) -> Result<Url, CriticalErrorKind> {
+    let url = Url::parse(&template.replace(HOST_PLACEHOLDER, host))?;
+    if url.cannot_be_a_base() {
+        return Err(CriticalErrorKind::InvalidBaseUrl(template.to_string()));
+    }
+    Ok(url)
+}
+
+/// Append each component of a path to an URL, percent-encoding them
+pub fn join_path(mut url: Url, path: &Path) -> Result<Url, CriticalErrorKind> {
+    let base = url.to_string();
+    {
+        let mut segments = url
+            .path_segments_mut()
+            .map_err(|()| CriticalErrorKind::InvalidBaseUrl(base))?;
+        segments.pop_if_empty();
+        for component in path.components() {
+            if let std::path::Component::Normal(segment) = component {
+                segments.push(&segment.to_string_lossy());
+            }
+        }
+    }
+    Ok(url)
+}
+
+/// HTTP link of a path served as is, without any base URL configured
+pub fn default_http_link(host: &str, path: &Path) -> Result<Url, CriticalErrorKind> {
+    join_path(base_url(DEFAULT_HTTP_BASE, host)?, path)
+}
+
+pub fn validate_http_base(http_base: &str) -> Result<HttpBase, String> {
+    let (folder, template) = match http_base.split_once('=') {
+        Some((folder, template)) if !folder.contains("://") => {
+            (Some(folder.to_string()), template.to_string())
+        }
+        _ => (None, http_base.to_string()),
+    };
+    if let Err(e) = base_url(&template, "localhost") {
+        return Err(format!("{http_base} is an invalid base URL : {e}"));
+    }
+    Ok(HttpBase { folder, template })
+}
+
+#[test]
+fn render_template_tests() {
+    let value = |key: &str| match key {
+        "username" => Some("user".to_string()),
+        "folder_rel" => Some("AC DC/#1.flac".to_string()),
+        _ => None,
+    };
+    assert_eq!(
+        render_template("smb://nas/{folder_rel:url}", value).unwrap(),
+        "smb://nas/AC%20DC/%231.flac"
+    );
+    assert_eq!(
+        render_template("{username}@nas:{folder_rel:shell} {{raw}}", value).unwrap(),
+        "user@nas:AC\\ DC/\\#1.flac {raw}"
+    );
+    assert!(render_template("{unknown}", value).is_err());
+    assert!(render_template("{username:upper}", value).is_err());
+    assert_eq!(
+        render_template("{username:06}|{username:5}", value).unwrap(),
+        "00user| user"
+    );
+    assert!(render_template("{username", value).is_err());
+    assert_eq!(
+        file_uri("/home/user/AC DC/#1.flac").unwrap(),
+        "file:///home/user/AC%20DC/%231.flac"
+    );
+    assert!(validate_link_template("sftp=sftp://{username}@{ipv4}{path:url}").is_ok());
+    assert!(validate_link_template("sftp=sftp://{user}").is_err());
+}
@@ -0,0 +1,144 @@
+use serde::Serialize;
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+use super::config::Config;
+use super::errors::CriticalErrorKind;
+use super::filter::Filters;
+use super::folders::local_files;
+use super::helpers::ReportOutput;
+use crate::fingerprinting::spectrum::{SourceQuality, Spectrum};
+
+/// Extensions of lossless files, decodable by Rodio
+const LOSSLESS_EXTENSIONS: [&str; 2] = ["flac", "wav"];
+
+#[derive(clap::Parser)]
+#[clap(about = "Detect lossless musics transcoded from lossy or upsampled files")]
+pub struct Lossless {
+    /// Only report suspicious files
+    #[clap(long)]
+    suspicious: bool,
+
+    /// Store verdicts in musics, for the spectrum filter to select suspicious ones
+    #[clap(long)]
+    store: bool,
+
+    /// Report format
+    #[clap(long, value_enum, default_value_t)]
+    output: ReportOutput,
+
+    /// Filters selecting musics, when no folder is given
+    #[clap(flatten)]
+    filters: Filters,
+
+    /// Folders to analyze instead of filtered musics
+    folders: Vec<String>,
+}
+
+#[derive(Serialize, Tabled, Debug)]
+pub struct SpectrumReport {
+    pub path: String,
+    pub sample_rate: u32,
+    /// Frequency of the hard cutoff, in Hz
+    #[tabled(display("display_cutoff"))]
+    pub cutoff: Option<f32>,
+    pub verdict: SourceQuality,
+}
+
+#[allow(clippy::ref_option, clippy::trivially_copy_pass_by_ref)]
+fn display_cutoff(cutoff: &Option<f32>) -> String {
+    cutoff
+        .map(|cutoff| format!("{:.1} kHz", cutoff / 1000.0))
+        .unwrap_or_default()
+}
+
+impl SpectrumReport {
+    pub fn new(path: &str) -> Result<Self, CriticalErrorKind> {
+        let spectrum = Spectrum::from_file(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            sample_rate: spectrum.sample_rate,
+            cutoff: spectrum.cutoff(),
+            verdict: spectrum.source_quality(),
+        })
+    }
+}
+
+fn is_lossless(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| LOSSLESS_EXTENSIONS.contains(&ext.as_str()))
+}
+
+impl Lossless {
+    pub async fn lossless(&self, config: Config) -> Result<(), CriticalErrorKind> {
+        let files = Box::pin(local_files(&config, &self.folders, &self.filters))
+            .await?
+            .into_iter()
+            .filter(|(_, path)| is_lossless(path))
+            .collect::<Vec<_>>();
+
+        let analyze_bar = indicatif::ProgressBar::new(files.len() as u64);
+        analyze_bar.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template(
+                    "[{elapsed_precise}] Analyzing files: {bar:50.cyan/blue} {pos:>7}/{len:7} {msg}",
+                )?
+                .progress_chars("##-"),
+        );
+        let mut reports = Vec::new();
+        for (_, path) in files {
+            scopeguard::defer! {analyze_bar.inc(1)};
+            let analyzed_path = path.clone();
+            let report =
+                match tokio::task::spawn_blocking(move || SpectrumReport::new(&analyzed_path))
+                    .await?
+                {
+                    Ok(report) => report,
+                    Err(e) => {
+                        analyze_bar.suspend(|| eprintln!("{path} : {e}"));
+                        continue;
+                    }
+                };
+            if self.store && !config.dry {
+                Box::pin(store(&config, &report)).await?;
+            }
+            if !self.suspicious || report.verdict != SourceQuality::Lossless {
+                reports.push(report);
+            }
+        }
+        analyze_bar.finish_and_clear();
+
+        match self.output {
+            ReportOutput::Table => println!("{}", Table::new(&reports)),
+            ReportOutput::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        }
+        Ok(())
+    }
+}
+
+async fn store(config: &Config, report: &SpectrumReport) -> Result<(), CriticalErrorKind> {
+    let _: Vec<uuid::Uuid> = Box::pin(config.gel.query(
+        STORE_SPECTRUM_QUERY,
+        &(
+            &report.path,
+            report.verdict.to_string(),
+            report.cutoff.map(f64::from),
+        ),
+    ))
+    .await?;
+    Ok(())
+}
+
+const STORE_SPECTRUM_QUERY: &str = r"
+select (
+    update Music
+    filter <str>$0 in .folders@path
+    set {
+        spectrum_verdict := <str>$1,
+        spectrum_cutoff := <optional float64>$2,
+        spectrum_checked := datetime_current()
+    }
+).id
+";
@@ -0,0 +1,403 @@
+use ebur128::{EbuR128, Mode};
+use gel_derive::Queryable;
+use rodio::Source;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufReader;
+use tabled::{Table, Tabled};
+
+use super::config::Config;
+use super::errors::CriticalErrorKind;
+use super::filter::Filters;
+use super::folders::local_files;
+use super::helpers::ReportOutput;
+use super::music_file::open_music_file;
+
+/// ReplayGain 2.0 reference loudness, in LUFS
+const REFERENCE_LOUDNESS: f64 = -18.0;
+/// Frames decoded before each meter update
+const METERED_FRAMES: usize = 4096;
+pub const REPLAYGAIN_TRACK_GAIN: &str = "REPLAYGAIN_TRACK_GAIN";
+pub const REPLAYGAIN_TRACK_PEAK: &str = "REPLAYGAIN_TRACK_PEAK";
+pub const REPLAYGAIN_ALBUM_GAIN: &str = "REPLAYGAIN_ALBUM_GAIN";
+pub const REPLAYGAIN_ALBUM_PEAK: &str = "REPLAYGAIN_ALBUM_PEAK";
+
+#[derive(clap::Parser)]
+#[clap(about = "Measure EBU R128 loudness of tracks and albums")]
+pub struct Loudness {
+    /// Write ReplayGain tags in music files
+    #[clap(long)]
+    write_tags: bool,
+
+    /// Store loudness and ReplayGain values in musics
+    #[clap(long)]
+    store: bool,
+
+    /// Report format
+    #[clap(long, value_enum, default_value_t)]
+    output: ReportOutput,
+
+    /// Filters selecting musics, when no folder is given
+    #[clap(flatten)]
+    filters: Filters,
+
+    /// Folders to measure instead of filtered musics
+    folders: Vec<String>,
+}
+
+/// EBU R128 measures of a track or an album
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct LoudnessMeasure {
+    /// Integrated loudness, in LUFS
+    pub integrated: f64,
+    /// Loudness range, in LU
+    pub range: f64,
+    /// True peak, linear with 1.0 at full scale
+    pub true_peak: f64,
+}
+
+impl LoudnessMeasure {
+    fn new(meter: &EbuR128) -> Result<Self, CriticalErrorKind> {
+        let mut true_peak = 0.0f64;
+        for channel in 0..meter.channels() {
+            true_peak = true_peak.max(meter.true_peak(channel)?);
+        }
+        Ok(Self {
+            integrated: meter.loudness_global()?,
+            range: meter.loudness_range()?,
+            true_peak,
+        })
+    }
+
+    /// Album measures, gated over all its tracks
+    fn album(meters: &[EbuR128], tracks: &[Self]) -> Result<Self, CriticalErrorKind> {
+        Ok(Self {
+            integrated: EbuR128::loudness_global_multiple(meters.iter())?,
+            range: EbuR128::loudness_range_multiple(meters.iter())?,
+            true_peak: tracks
+                .iter()
+                .map(|track| track.true_peak)
+                .fold(0.0, f64::max),
+        })
+    }
+
+    /// Gain bringing loudness to the ReplayGain reference, none for silence
+    #[must_use]
+    pub fn gain(&self) -> Option<f64> {
+        self.integrated
+            .is_finite()
+            .then_some(REFERENCE_LOUDNESS - self.integrated)
+    }
+}
+
+/// Meter fed with interleaved samples
+pub fn meter(
+    samples: impl Iterator<Item = i16>,
+    channels: u16,
+    sample_rate: u32,
+) -> Result<EbuR128, CriticalErrorKind> {
+    let mut meter = EbuR128::new(
+        u32::from(channels),
+        sample_rate,
+        Mode::I | Mode::LRA | Mode::TRUE_PEAK,
+    )?;
+    let chunk_len = METERED_FRAMES * usize::from(channels);
+    let mut chunk = Vec::with_capacity(chunk_len);
+    for sample in samples {
+        chunk.push(sample);
+        if chunk.len() == chunk_len {
+            meter.add_frames_i16(&chunk)?;
+            chunk.clear();
+        }
+    }
+    // An incomplete last frame is dropped
+    chunk.truncate(chunk.len() - chunk.len() % usize::from(channels.max(1)));
+    meter.add_frames_i16(&chunk)?;
+    Ok(meter)
+}
+
+fn meter_file(path: &str) -> Result<EbuR128, CriticalErrorKind> {
+    let decoder = rodio::Decoder::new(BufReader::new(std::fs::File::open(path)?))?;
+    let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+    meter(decoder, channels, sample_rate)
+}
+
+/// ReplayGain tags values of a track
+#[derive(Clone, Copy, Debug)]
+pub struct ReplayGain {
+    pub track_gain: f64,
+    pub track_peak: f64,
+    pub album_gain: f64,
+    pub album_peak: f64,
+}
+
+impl ReplayGain {
+    /// Tags and their value, formatted like other ReplayGain scanners do
+    #[must_use]
+    pub fn tags(&self) -> [(&'static str, String); 4] {
+        [
+            (REPLAYGAIN_TRACK_GAIN, format!("{:+.2} dB", self.track_gain)),
+            (REPLAYGAIN_TRACK_PEAK, format!("{:.6}", self.track_peak)),
+            (REPLAYGAIN_ALBUM_GAIN, format!("{:+.2} dB", self.album_gain)),
+            (REPLAYGAIN_ALBUM_PEAK, format!("{:.6}", self.album_peak)),
+        ]
+    }
+}
+
+#[derive(Serialize, Tabled, Debug)]
+pub struct LoudnessReport {
+    pub path: String,
+    pub album: String,
+    #[tabled(display("display_track"))]
+    pub track: LoudnessMeasure,
+    #[tabled(display("display_gain"))]
+    pub track_gain: Option<f64>,
+    #[tabled(skip)]
+    pub album_measure: LoudnessMeasure,
+    #[tabled(display("display_gain"))]
+    pub album_gain: Option<f64>,
+}
+
+fn display_track(measure: &LoudnessMeasure) -> String {
+    format!(
+        "{:.1} LUFS, {:.1} LU, {:.1} dBTP",
+        measure.integrated,
+        measure.range,
+        20.0 * measure.true_peak.log10()
+    )
+}
+
+#[allow(clippy::ref_option, clippy::trivially_copy_pass_by_ref)]
+fn display_gain(gain: &Option<f64>) -> String {
+    gain.map(|gain| format!("{gain:+.2} dB"))
+        .unwrap_or_default()
+}
+
+impl LoudnessReport {
+    fn replay_gain(&self) -> Option<ReplayGain> {
+        Some(ReplayGain {
+            track_gain: self.track_gain?,
+            track_peak: self.track.true_peak,
+            album_gain: self.album_gain?,
+            album_peak: self.album_measure.true_peak,
+        })
+    }
+}
+
+#[derive(Queryable)]
+struct AlbumPaths {
+    album_id: uuid::Uuid,
+    album_name: String,
+    paths: Vec<String>,
+}
+
+/// Album name and id, and file path for files without album, keying the files of an album
+type AlbumKey = (String, Option<uuid::Uuid>, String);
+
+/// Files grouped by their album in the library, files without album or out of the library
+/// standing alone
+async fn albums(
+    config: &Config,
+    files: Vec<(String, String)>,
+) -> Result<BTreeMap<AlbumKey, Vec<(String, String)>>, CriticalErrorKind> {
+    let mut library_albums = HashMap::new();
+    if !config.no_gel {
+        let paths = files
+            .iter()
+            .map(|(_, path)| path.clone())
+            .collect::<Vec<_>>();
+        let musics: Vec<AlbumPaths> =
+            Box::pin(config.gel.query(ALBUM_PATHS_QUERY, &(paths,))).await?;
+        for music in musics {
+            if music.album_name.is_empty() {
+                continue;
+            }
+            for path in music.paths {
+                library_albums.insert(path, (music.album_name.clone(), music.album_id));
+            }
+        }
+    }
+
+    let mut albums = BTreeMap::<_, Vec<_>>::new();
+    for (folder, path) in files {
+        let key = match library_albums.get(&path) {
+            Some((name, id)) => (name.clone(), Some(*id), String::new()),
+            None => (String::new(), None, path.clone()),
+        };
+        albums.entry(key).or_default().push((folder, path));
+    }
+    Ok(albums)
+}
+
+impl Loudness {
+    pub async fn loudness(&self, config: Config) -> Result<(), CriticalErrorKind> {
+        let files = Box::pin(local_files(&config, &self.folders, &self.filters)).await?;
+
+        let measure_bar = indicatif::ProgressBar::new(files.len() as u64);
+        measure_bar.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template(
+                    "[{elapsed_precise}] Measuring files: {bar:50.cyan/blue} {pos:>7}/{len:7} {msg}",
+                )?
+                .progress_chars("##-"),
+        );
+        let mut reports = Vec::new();
+        for ((album, _, _), tracks) in Box::pin(albums(&config, files)).await? {
+            let mut meters = Vec::new();
+            let mut measured = Vec::new();
+            for (folder, path) in tracks {
+                scopeguard::defer! {measure_bar.inc(1)};
+                let metered_path = path.clone();
+                let metered = tokio::task::spawn_blocking(move || {
+                    let meter = meter_file(&metered_path)?;
+                    let measure = LoudnessMeasure::new(&meter)?;
+                    Ok::<_, CriticalErrorKind>((meter, measure))
+                })
+                .await?;
+                match metered {
+                    Ok((meter, measure)) => {
+                        meters.push(meter);
+                        measured.push((folder, path, measure));
+                    }
+                    Err(e) => measure_bar.suspend(|| eprintln!("{path} : {e}")),
+                }
+            }
+            if measured.is_empty() {
+                continue;
+            }
+
+            let tracks = measured
+                .iter()
+                .map(|(_, _, measure)| *measure)
+                .collect::<Vec<_>>();
+            let album_measure = LoudnessMeasure::album(&meters, &tracks)?;
+            for (folder, path, track) in measured {
+                let report = LoudnessReport {
+                    path,
+                    album: album.clone(),
+                    track,
+                    track_gain: track.gain(),
+                    album_measure,
+                    album_gain: album_measure.gain(),
+                };
+                if !config.dry
+                    && let Some(replay_gain) = report.replay_gain()
+                {
+                    if self.write_tags
+                        && let Err(e) = write_tags(&folder, &report.path, &replay_gain)
+                    {
+                        measure_bar.suspend(|| eprintln!("{} : {e}", report.path));
+                    }
+                    if self.store {
+                        Box::pin(store(&config, &report, &replay_gain)).await?;
+                    }
+                }
+                reports.push(report);
+            }
+        }
+        measure_bar.finish_and_clear();
+
+        match self.output {
+            ReportOutput::Table => println!("{}", Table::new(&reports)),
+            ReportOutput::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        }
+        Ok(())
+    }
+}
+
+fn write_tags(folder: &str, path: &str, replay_gain: &ReplayGain) -> Result<(), CriticalErrorKind> {
+    let mut music_file = open_music_file(folder, path)?;
+    music_file.set_replay_gain(replay_gain);
+    music_file.save()
+}
+
+async fn store(
+    config: &Config,
+    report: &LoudnessReport,
+    replay_gain: &ReplayGain,
+) -> Result<(), CriticalErrorKind> {
+    let _: Vec<uuid::Uuid> = Box::pin(config.gel.query(
+        STORE_LOUDNESS_QUERY,
+        &(
+            &report.path,
+            report.track.integrated,
+            report.track.range,
+            replay_gain.track_gain,
+            replay_gain.track_peak,
+            replay_gain.album_gain,
+            replay_gain.album_peak,
+        ),
+    ))
+    .await?;
+    Ok(())
+}
+
+const ALBUM_PATHS_QUERY: &str = r"
+with paths := array_unpack(<array<str>>$0)
+select Music {
+    album_id := .album.id,
+    album_name := .album.name,
+    paths := array_agg(.folders@path)
+}
+filter any(.folders@path in paths)
+";
+
+const STORE_LOUDNESS_QUERY: &str = r"
+select (
+    update Music
+    filter <str>$0 in .folders@path
+    set {
+        loudness := <float64>$1,
+        loudness_range := <float64>$2,
+        replaygain_track_gain := <float64>$3,
+        replaygain_track_peak := <float64>$4,
+        replaygain_album_gain := <float64>$5,
+        replaygain_album_peak := <float64>$6
+    }
+).id
+";
+
+#[test]
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn loudness_tests() {
+    // 1 KHz stereo sine at -20 dBFS, measured at -20 LUFS
+    let sine = |amplitude: f64| {
+        (0..48000 * 5).flat_map(move |n| {
+            let sample = (amplitude
+                * f64::from(i16::MAX)
+                * (std::f64::consts::TAU * 1000.0 * f64::from(n) / 48000.0).sin())
+                as i16;
+            [sample, sample]
+        })
+    };
+    let quiet = meter(sine(0.1), 2, 48000).unwrap();
+    let loud = meter(sine(0.2), 2, 48000).unwrap();
+    let track = LoudnessMeasure::new(&quiet).unwrap();
+    assert!((track.integrated + 20.0).abs() < 0.1, "{track:?}");
+    assert!((track.gain().unwrap() - 2.0).abs() < 0.1);
+    assert!((track.true_peak - 0.1).abs() < 0.01);
+
+    let tracks = [track, LoudnessMeasure::new(&loud).unwrap()];
+    let album = LoudnessMeasure::album(&[quiet, loud], &tracks).unwrap();
+    assert!(album.integrated > tracks[0].integrated && album.integrated < tracks[1].integrated);
+    assert!((album.true_peak - tracks[1].true_peak).abs() < f64::EPSILON);
+
+    let silence = meter(std::iter::repeat_n(0, 48000), 1, 48000).unwrap();
+    assert_eq!(LoudnessMeasure::new(&silence).unwrap().gain(), None);
+
+    let replay_gain = ReplayGain {
+        track_gain: 5.0,
+        track_peak: 0.1,
+        album_gain: -1.234,
+        album_peak: 0.5,
+    };
+    assert_eq!(
+        replay_gain.tags(),
+        [
+            (REPLAYGAIN_TRACK_GAIN, "+5.00 dB".to_string()),
+            (REPLAYGAIN_TRACK_PEAK, "0.100000".to_string()),
+            (REPLAYGAIN_ALBUM_GAIN, "-1.23 dB".to_string()),
+            (REPLAYGAIN_ALBUM_PEAK, "0.500000".to_string()),
+        ]
+    );
+}
@@ -4,22 +4,37 @@ pub mod bests;
 pub mod cache;
 pub mod clean;
 pub mod config;
+pub mod duplicates;
 pub mod errors;
+pub mod export;
 pub mod filter;
+pub mod fingerprints;
 pub mod flac_file;
 pub mod folders;
 pub mod genres;
 pub mod helpers;
 pub mod keywords;
+pub mod links;
+pub mod lossless;
+pub mod loudness;
 pub mod mp3_file;
+pub mod mpd;
 pub mod music;
 pub mod music_file;
 pub mod music_result;
+pub mod play;
 pub mod playlist;
 pub mod ratings;
+pub mod recognize;
 pub mod remove;
 pub mod scan;
 pub mod search;
 pub mod shazam;
 pub mod stats;
+pub mod tracklist;
+#[cfg(feature = "ffmpeg")]
+pub mod transcode;
+pub mod verify;
+pub mod verify_tags;
 pub mod vertex;
+pub mod vote;
@@ -1,12 +1,17 @@
 use id3::Tag as Mp3Tag;
 use id3::TagLike;
+use id3::frame::{Comment, ExtendedText};
 use mp3_duration;
 use num_traits::ToPrimitive;
 
 use super::errors::CriticalErrorKind;
-use super::music_file::MusicFile;
+use super::loudness::ReplayGain;
+use super::music_file::{MusicFile, MusicFileMut};
 use super::ratings::Rating;
 
+const FMPS_RATING: &str = "FMPS_Rating";
+const KEYWORDS_LANG: &str = "eng";
+
 pub struct Mp3File {
     folder: String,
     path: String,
@@ -23,6 +28,57 @@ impl Mp3File {
     }
 }
 
+impl MusicFileMut for Mp3File {
+    fn set_rating(&mut self, rating: Rating) {
+        let fmps_rating: f64 = rating.into();
+        // Also drop the upper case frame written by some encoders
+        self.tag.remove_extended_text(Some(FMPS_RATING), None);
+        self.tag
+            .remove_extended_text(Some(&FMPS_RATING.to_uppercase()), None);
+        self.tag.add_frame(ExtendedText {
+            description: FMPS_RATING.to_string(),
+            value: (fmps_rating / 5.0).to_string(),
+        });
+    }
+
+    fn set_keywords(&mut self, keywords: &[String]) {
+        self.tag.remove("COMM");
+        self.tag.add_frame(Comment {
+            lang: KEYWORDS_LANG.to_string(),
+            description: String::new(),
+            text: keywords.join(" "),
+        });
+    }
+
+    fn set_artist(&mut self, artist: &str) {
+        self.tag.set_artist(artist);
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.tag.set_title(title);
+    }
+
+    fn set_album(&mut self, album: &str) {
+        self.tag.set_album(album);
+    }
+
+    fn set_replay_gain(&mut self, replay_gain: &ReplayGain) {
+        for (key, value) in replay_gain.tags() {
+            self.tag.remove_extended_text(Some(key), None);
+            self.tag
+                .remove_extended_text(Some(&key.to_lowercase()), None);
+            self.tag.add_frame(ExtendedText {
+                description: key.to_string(),
+                value,
+            });
+        }
+    }
+
+    fn save(&mut self) -> Result<(), CriticalErrorKind> {
+        Ok(self.tag.write_to_path(&self.path, id3::Version::Id3v24)?)
+    }
+}
+
 impl MusicFile for Mp3File {
     fn path(&self) -> &str {
         &self.path
@@ -66,14 +122,13 @@ impl MusicFile for Mp3File {
 
     fn rating(&self) -> Result<Rating, CriticalErrorKind> {
         for frame in self.tag.frames() {
-            if let Some(extended_text) = frame.content().extended_text() {
-                if extended_text.description == "FMPS_Rating" {
-                    if let Ok(mut rating) = extended_text.value.to_string().parse::<f64>() {
-                        rating *= 5.0;
-                        let rating = Rating::try_from(rating)?;
-                        return Ok(rating);
-                    }
-                }
+            if let Some(extended_text) = frame.content().extended_text()
+                && extended_text.description == FMPS_RATING
+                && let Ok(mut rating) = extended_text.value.clone().parse::<f64>()
+            {
+                rating *= 5.0;
+                let rating = Rating::try_from(rating)?;
+                return Ok(rating);
             }
         }
         Ok(Rating::default())
@@ -81,7 +136,7 @@ impl MusicFile for Mp3File {
 
     fn keywords(&self) -> Vec<String> {
         for comment in self.tag.comments() {
-            if comment.lang == "eng" {
+            if comment.lang == KEYWORDS_LANG {
                 return comment
                     .text
                     .split_whitespace()
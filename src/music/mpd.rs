@@ -0,0 +1,267 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::Path;
+
+use super::errors::CriticalErrorKind;
+use super::links::{LINK_PLACEHOLDERS, render_template};
+use super::music_result::MusicResult;
+
+const DEFAULT_MPD_HOST: &str = "localhost:6600";
+const DEFAULT_MPD_URI: &str = "{folder_rel}";
+const GREETING: &str = "OK MPD ";
+
+#[derive(clap::Parser, Clone)]
+pub struct MpdOptions {
+    /// MPD server address
+    #[clap(long = "mpd-host", default_value = DEFAULT_MPD_HOST)]
+    host: String,
+
+    /// Append to the MPD queue instead of replacing it
+    #[clap(long = "mpd-append")]
+    append: bool,
+
+    /// MPD URI of musics, [FOLDER=]TEMPLATE relative to the MPD music directory, with link placeholders like {folder_rel}
+    #[clap(long = "mpd-uri", value_parser = validate_mpd_uri)]
+    uris: Vec<MpdUri>,
+
+    /// Save the queue as a stored MPD playlist
+    #[clap(long = "mpd-playlist")]
+    playlist: Option<String>,
+}
+
+impl Default for MpdOptions {
+    fn default() -> Self {
+        Self {
+            host: DEFAULT_MPD_HOST.to_string(),
+            append: false,
+            uris: Vec::new(),
+            playlist: None,
+        }
+    }
+}
+
+impl MpdOptions {
+    /// MPD URI of a music, from its first folder having a mapping
+    pub fn uri(&self, music: &MusicResult) -> Result<Option<String>, CriticalErrorKind> {
+        let default_uri = MpdUri {
+            folder: None,
+            template: DEFAULT_MPD_URI.to_string(),
+        };
+        let uris = if self.uris.is_empty() {
+            std::slice::from_ref(&default_uri)
+        } else {
+            &self.uris
+        };
+        for folder in &music.folders {
+            let mpd_uri = uris
+                .iter()
+                .find(|uri| {
+                    uri.folder
+                        .as_ref()
+                        .is_some_and(|f| Path::new(f) == Path::new(&folder.name))
+                })
+                .or_else(|| uris.iter().find(|uri| uri.folder.is_none()));
+            if let Some(mpd_uri) = mpd_uri {
+                return Ok(Some(music.render(folder, &mpd_uri.template)?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Replace or extend the MPD queue with musics, then save it if asked
+    pub fn push(&self, musics: &[MusicResult], dry: bool) -> Result<(), CriticalErrorKind> {
+        let mut uris = Vec::new();
+        for music in musics {
+            match self.uri(music)? {
+                Some(uri) => uris.push(uri),
+                None => eprintln!("{music} : no MPD URI mapping, skipping"),
+            }
+        }
+        if dry {
+            for uri in &uris {
+                println!("Add {uri}");
+            }
+            return Ok(());
+        }
+
+        let mut client = MpdClient::connect(&self.host)?;
+        client.command("command_list_ok_begin")?;
+        if !self.append {
+            client.command("clear")?;
+        }
+        for uri in &uris {
+            client.command(&format!("add {}", quote(uri)))?;
+        }
+        client.command("command_list_end")?;
+        client.response()?;
+
+        if let Some(playlist) = &self.playlist {
+            // Saving fails when the playlist exists, it may not
+            let _ = client.request(&format!("rm {}", quote(playlist)));
+            client.request(&format!("save {}", quote(playlist)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Minimal client of the MPD text protocol
+pub struct MpdClient {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl MpdClient {
+    pub fn connect(host: &str) -> Result<Self, CriticalErrorKind> {
+        let writer = TcpStream::connect(host)?;
+        let mut reader = BufReader::new(writer.try_clone()?);
+        let mut greeting = String::new();
+        reader.read_line(&mut greeting)?;
+        if !greeting.starts_with(GREETING) {
+            return Err(CriticalErrorKind::MpdError(format!(
+                "{host} is not a MPD server : {}",
+                greeting.trim_end()
+            )));
+        }
+        Ok(Self { reader, writer })
+    }
+
+    /// Send a command without waiting for its response, for command lists
+    pub fn command(&mut self, command: &str) -> Result<(), CriticalErrorKind> {
+        writeln!(self.writer, "{command}")?;
+        Ok(())
+    }
+
+    /// Read lines until OK, failing on ACK
+    pub fn response(&mut self) -> Result<Vec<String>, CriticalErrorKind> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(CriticalErrorKind::MpdError("connection closed".to_string()));
+            }
+            let line = line.trim_end();
+            if line == "OK" {
+                return Ok(lines);
+            }
+            if line.starts_with("ACK ") {
+                return Err(CriticalErrorKind::MpdError(line.to_string()));
+            }
+            if line != "list_OK" {
+                lines.push(line.to_string());
+            }
+        }
+    }
+
+    pub fn request(&mut self, command: &str) -> Result<Vec<String>, CriticalErrorKind> {
+        self.command(command)?;
+        self.response()
+    }
+}
+
+/// Quote a command argument, escaping backslashes and double quotes
+#[must_use]
+pub fn quote(argument: &str) -> String {
+    format!(
+        "\"{}\"",
+        argument.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+/// Template mapping a folder root (or every folder) to URIs in the MPD music directory
+#[derive(Clone, Debug, PartialEq)]
+pub struct MpdUri {
+    folder: Option<String>,
+    template: String,
+}
+
+pub fn validate_mpd_uri(mpd_uri: &str) -> Result<MpdUri, String> {
+    let (folder, template) = match mpd_uri.split_once('=') {
+        Some((folder, template)) if Path::new(folder).is_absolute() => {
+            (Some(folder.to_string()), template.to_string())
+        }
+        _ => (None, mpd_uri.to_string()),
+    };
+    if let Err(e) = render_template(&template, |key| {
+        LINK_PLACEHOLDERS.contains(&key).then(String::new)
+    }) {
+        return Err(format!(
+            "{mpd_uri} : {e}, valid placeholders: {}",
+            LINK_PLACEHOLDERS.join(", ")
+        ));
+    }
+    Ok(MpdUri { folder, template })
+}
+
+#[test]
+fn mpd_tests() {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let host = listener.local_addr().unwrap().to_string();
+
+    // Fake MPD server, recording commands and refusing to remove a missing playlist
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        writer.write_all(b"OK MPD 0.23.5\n").unwrap();
+        let mut commands = Vec::new();
+        for line in BufReader::new(stream).lines() {
+            let line = line.unwrap();
+            let response: &[u8] = match line.as_str() {
+                "command_list_ok_begin" | "clear" => b"",
+                "command_list_end" => b"OK\n",
+                _ if line.starts_with("add ") => b"list_OK\n",
+                _ if line.starts_with("rm ") => b"ACK [50@0] {rm} No such playlist\n",
+                _ => b"OK\n",
+            };
+            writer.write_all(response).unwrap();
+            commands.push(line);
+        }
+        commands
+    });
+
+    let music = crate::test_helpers::music(
+        "Song",
+        "/home/user/Music",
+        "/home/user/Music/AC \"DC\"/a.flac",
+    );
+    let mpd_options = MpdOptions {
+        host,
+        playlist: Some("best".to_string()),
+        ..MpdOptions::default()
+    };
+    assert_eq!(
+        mpd_options.uri(&music).unwrap().unwrap(),
+        "AC \"DC\"/a.flac"
+    );
+    mpd_options
+        .push(std::slice::from_ref(&music), false)
+        .unwrap();
+    drop(mpd_options);
+    assert_eq!(
+        server.join().unwrap(),
+        vec![
+            "command_list_ok_begin",
+            "clear",
+            "add \"AC \\\"DC\\\"/a.flac\"",
+            "command_list_end",
+            "rm \"best\"",
+            "save \"best\"",
+        ]
+    );
+
+    let mpd_options = MpdOptions {
+        uris: vec![validate_mpd_uri("/home/user/Music=nas/{folder_rel}").unwrap()],
+        ..MpdOptions::default()
+    };
+    assert_eq!(
+        mpd_options.uri(&music).unwrap().unwrap(),
+        "nas/AC \"DC\"/a.flac"
+    );
+    let mpd_options = MpdOptions {
+        uris: vec![validate_mpd_uri("/mnt/other={folder_rel}").unwrap()],
+        ..MpdOptions::default()
+    };
+    assert!(mpd_options.uri(&music).unwrap().is_none());
+    assert!(validate_mpd_uri("{unknown}").is_err());
+}
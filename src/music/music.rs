@@ -88,6 +88,20 @@ select upsert_music(
     path := <str>$9
 ).id";
 
+pub const UPDATE_RATING_QUERY: &str = "
+update Music
+filter <str>$0 in .folders@path
+set {
+    rating := <Rating>$1
+}";
+
+pub const UPDATE_KEYWORDS_QUERY: &str = "
+update Music
+filter <str>$0 in .folders@path
+set {
+    keywords := (select Keyword filter .id in array_unpack(<array<uuid>>$1))
+}";
+
 pub const MUSIC_FIELDS: &str = r"
 name,
 artist_name := .artist.name,
@@ -1,8 +1,13 @@
 use std::fs;
+use std::path::Path;
 
+use super::flac_file::FlacFile;
+use super::loudness::ReplayGain;
+use super::mp3_file::Mp3File;
 use super::{errors::CriticalErrorKind, ratings::Rating};
 
 pub type BoxMusicFile = Box<dyn MusicFile + Send + Sync>;
+pub type BoxMusicFileMut = Box<dyn MusicFileMut + Send + Sync>;
 
 #[async_trait::async_trait]
 pub trait MusicFile {
@@ -27,6 +32,26 @@ pub trait MusicFile {
     }
 }
 
+/// Tags edition, written back to the file on save
+pub trait MusicFileMut: MusicFile {
+    fn set_rating(&mut self, rating: Rating);
+    fn set_keywords(&mut self, keywords: &[String]);
+    fn set_artist(&mut self, artist: &str);
+    fn set_title(&mut self, title: &str);
+    fn set_album(&mut self, album: &str);
+    fn set_replay_gain(&mut self, replay_gain: &ReplayGain);
+    fn save(&mut self) -> Result<(), CriticalErrorKind>;
+}
+
+/// Open a music file for tags edition, depending on its extension
+pub fn open_music_file(folder: &str, path: &str) -> Result<BoxMusicFileMut, CriticalErrorKind> {
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("flac") => Ok(Box::new(FlacFile::from_path(folder, path)?)),
+        Some("mp3") => Ok(Box::new(Mp3File::from_path(folder, path)?)),
+        _ => Err(CriticalErrorKind::UnsupportedFormat(path.to_string())),
+    }
+}
+
 impl std::fmt::Debug for dyn MusicFile + Sync {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let rt = tokio::runtime::Builder::new_current_thread()
@@ -4,10 +4,16 @@ use std::hash::{Hash, Hasher};
 use tabled::Tabled;
 
 use super::errors::CriticalErrorKind;
+use super::helpers::shell_escape;
+use super::links::{
+    HttpBase, LinkOptions, default_http_link, file_uri, join_path, render_template,
+};
 use super::music_file::MusicFile;
 use super::playlist::Kind;
 use super::ratings::Rating;
 
+const LOCALHOST: &str = "localhost";
+
 #[derive(Queryable, Serialize, Clone)]
 pub struct FolderResult {
     pub name: String,
@@ -17,36 +23,54 @@ pub struct FolderResult {
 }
 
 impl FolderResult {
+    pub fn relative_path(&self) -> Result<&std::path::Path, CriticalErrorKind> {
+        let base = std::path::Path::new(&self.name);
+        Ok(std::path::Path::new(&self.path).strip_prefix(base)?)
+    }
+
     pub fn effective_path(&self, relative: bool) -> Result<String, CriticalErrorKind> {
-        let path = if relative {
-            let base = std::path::Path::new(&self.name);
-            let path = std::path::Path::new(&self.path).strip_prefix(base)?;
-            path.display().to_string()
+        if relative {
+            Ok(self.relative_path()?.display().to_string())
         } else {
-            self.path.clone()
-        };
-        Ok(path.replace(' ', "\\ "))
+            Ok(self.path.clone())
+        }
     }
 
-    pub fn http_link(&self, relative: bool) -> Result<String, CriticalErrorKind> {
-        Ok(format!(
-            "http://{}/{}",
-            self.ipv4,
-            self.effective_path(relative)?
-        ))
+    pub fn http_link(
+        &self,
+        host: &str,
+        relative: bool,
+        http_bases: &[HttpBase],
+    ) -> Result<String, CriticalErrorKind> {
+        let url = if let Some(http_base) = HttpBase::find(http_bases, &self.name) {
+            join_path(http_base.url(host)?, self.relative_path()?)?
+        } else if relative {
+            default_http_link(host, self.relative_path()?)?
+        } else {
+            default_http_link(host, std::path::Path::new(&self.path))?
+        };
+        Ok(url.to_string())
     }
 
     #[must_use]
     pub fn local_ssh_link(&self) -> String {
-        format!("{}@localhost:{}", self.username, self.path)
+        format!("{}@localhost:{}", self.username, shell_escape(&self.path))
     }
 
     #[must_use]
     pub fn remote_ssh_link(&self) -> String {
-        format!("{}@{}:{}", self.username, self.ipv4, self.path)
+        format!(
+            "{}@{}:{}",
+            self.username,
+            self.ipv4,
+            shell_escape(&self.path)
+        )
     }
 
-    pub fn links(&self, relative: bool, kinds: &[Kind]) -> Result<Vec<String>, CriticalErrorKind> {
+    pub fn links(&self, link_options: &LinkOptions) -> Result<Vec<String>, CriticalErrorKind> {
+        let kinds = link_options.kinds();
+        let relative = link_options.relative();
+        let http_bases = link_options.http_bases();
         let mut paths = Vec::new();
         if kinds.contains(&Kind::All) || kinds.contains(&Kind::LocalSSH) {
             paths.push(self.local_ssh_link());
@@ -55,16 +79,21 @@ impl FolderResult {
             paths.push(self.remote_ssh_link());
         }
         if kinds.contains(&Kind::All) || kinds.contains(&Kind::LocalHTTP) {
-            paths.push(self.http_link(relative)?);
+            let host = link_options.http_host().unwrap_or(LOCALHOST);
+            paths.push(self.http_link(host, relative, http_bases)?);
         }
         if kinds.contains(&Kind::All) || kinds.contains(&Kind::RemoteHttp) {
-            paths.push(self.http_link(relative)?);
+            let host = link_options.http_host().unwrap_or(&self.ipv4);
+            paths.push(self.http_link(host, relative, http_bases)?);
         }
         if kinds.contains(&Kind::All) || kinds.contains(&Kind::Local) {
-            paths.push(self.effective_path(relative)?);
+            paths.push(link_options.local_path(&self.name, &self.path)?);
         }
         if kinds.contains(&Kind::All) || kinds.contains(&Kind::Remote) {
-            paths.push(self.effective_path(relative)?);
+            paths.push(link_options.local_path(&self.name, &self.path)?);
+        }
+        if kinds.contains(&Kind::All) || kinds.contains(&Kind::File) {
+            paths.push(file_uri(&self.path)?);
         }
         Ok(paths)
     }
@@ -149,14 +178,67 @@ impl MusicResult {
         )
     }
 
-    pub fn all_links(
+    fn placeholder(&self, folder: &FolderResult, folder_rel: &str, key: &str) -> Option<String> {
+        match key {
+            "folder" => Some(folder.name.clone()),
+            "folder_rel" => Some(folder_rel.to_string()),
+            "path" => Some(folder.path.clone()),
+            "username" => Some(folder.username.clone()),
+            "ipv4" => Some(folder.ipv4.clone()),
+            "title" => Some(self.name.clone()),
+            "artist" => Some(self.artist_name.clone()),
+            "album" => Some(self.album_name.clone()),
+            "genre" => Some(self.genre_name.clone()),
+            "track" => Some(self.track.to_string()),
+            "rating" => Some(self.rating.to_string()),
+            "length" => Some(self.length.to_string()),
+            "size" => Some(self.size.to_string()),
+            _ => None,
+        }
+    }
+
+    /// One line summary, like Artist - Album - 03 Title [3:25] rating 4.5
+    #[must_use]
+    pub fn now_playing(&self) -> String {
+        format!(
+            "{} - {} - {:02} {} [{}] rating {}",
+            self.artist_name,
+            self.album_name,
+            self.track,
+            self.name,
+            self.human_duration,
+            self.rating
+        )
+    }
+
+    /// Folders whose file of this music is present on this host
+    pub fn local_folders(&self) -> impl Iterator<Item = &FolderResult> {
+        self.folders
+            .iter()
+            .filter(|folder| std::path::Path::new(&folder.path).is_file())
+    }
+
+    /// Render a template with placeholders of this music, as stored in this folder
+    pub fn render(
         &self,
-        relative: bool,
-        kinds: &[Kind],
-    ) -> Result<Vec<String>, CriticalErrorKind> {
+        folder: &FolderResult,
+        template: &str,
+    ) -> Result<String, CriticalErrorKind> {
+        let folder_rel = folder.effective_path(true)?;
+        render_template(template, |key| self.placeholder(folder, &folder_rel, key))
+    }
+
+    pub fn all_links(&self, link_options: &LinkOptions) -> Result<Vec<String>, CriticalErrorKind> {
         let mut links = Vec::new();
         for folder in &self.folders {
-            links.extend_from_slice(folder.links(relative, kinds)?.as_slice());
+            links.extend_from_slice(folder.links(link_options)?.as_slice());
+            if link_options.link_templates().is_empty() {
+                continue;
+            }
+            let folder_rel = folder.effective_path(true)?;
+            for link_template in link_options.link_templates() {
+                links.push(link_template.render(|key| self.placeholder(folder, &folder_rel, key))?);
+            }
         }
         Ok(links)
     }
@@ -227,3 +309,45 @@ impl std::fmt::Display for MusicResult {
         write!(f, "{}", self.path())
     }
 }
+
+#[test]
+fn folder_links_tests() {
+    let folder = FolderResult {
+        name: "/home/user/Music".to_string(),
+        username: "user".to_string(),
+        ipv4: "10.0.0.1".to_string(),
+        path: "/home/user/Music/AC DC/#1 ?é.flac".to_string(),
+    };
+    assert_eq!(
+        folder.http_link(LOCALHOST, false, &[]).unwrap(),
+        "http://localhost/home/user/Music/AC%20DC/%231%20%3F%C3%A9.flac"
+    );
+    assert_eq!(
+        folder.http_link(&folder.ipv4, true, &[]).unwrap(),
+        "http://10.0.0.1/AC%20DC/%231%20%3F%C3%A9.flac"
+    );
+
+    let http_bases = [
+        crate::music::links::validate_http_base("https://{host}:8443/music/").unwrap(),
+        crate::music::links::validate_http_base("/home/user/Music/=http://nas:8080/share").unwrap(),
+    ];
+    assert_eq!(
+        folder.http_link(LOCALHOST, false, &http_bases).unwrap(),
+        "http://nas:8080/share/AC%20DC/%231%20%3F%C3%A9.flac"
+    );
+    assert_eq!(
+        folder
+            .http_link(LOCALHOST, false, &http_bases[..1])
+            .unwrap(),
+        "https://localhost:8443/music/AC%20DC/%231%20%3F%C3%A9.flac"
+    );
+
+    assert_eq!(
+        folder.effective_path(true).unwrap(),
+        "AC DC/#1 ?é.flac".to_string()
+    );
+    assert_eq!(
+        folder.remote_ssh_link(),
+        "user@10.0.0.1:/home/user/Music/AC\\ DC/\\#1\\ \\?é.flac"
+    );
+}
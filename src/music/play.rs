@@ -0,0 +1,348 @@
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use std::io::BufReader;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::config::Config;
+use super::errors::CriticalErrorKind;
+use super::export::local_source;
+use super::filter::Filters;
+use super::music::UPDATE_RATING_QUERY;
+use super::music_file::open_music_file;
+use super::music_result::MusicResult;
+use super::playlist::{Playlist, PlaylistOptions};
+use super::ratings::Rating;
+
+const PLAY_NAME: &str = "play";
+const SEEK_STEP: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+const CONTROLS: &str =
+    "space: pause, n: next, p: previous, left/right: seek 10s, 1-5: rate, q: quit";
+
+#[derive(clap::Parser)]
+#[clap(about = "Play musics on the default audio output")]
+pub struct Play {
+    /// Playlist options
+    #[clap(flatten)]
+    playlist_options: PlaylistOptions,
+
+    /// More filters
+    #[clap(flatten)]
+    filters: Filters,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Control {
+    TogglePause,
+    Next,
+    Previous,
+    SeekForward,
+    SeekBackward,
+    Rate(u8),
+    Quit,
+}
+
+impl Control {
+    #[must_use]
+    pub fn from_key(code: KeyCode, modifiers: KeyModifiers) -> Option<Self> {
+        match code {
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => Some(Control::Quit),
+            KeyCode::Char(' ') => Some(Control::TogglePause),
+            KeyCode::Char('n') => Some(Control::Next),
+            KeyCode::Char('p') => Some(Control::Previous),
+            KeyCode::Right => Some(Control::SeekForward),
+            KeyCode::Left => Some(Control::SeekBackward),
+            KeyCode::Char(c @ '1'..='5') => c
+                .to_digit(10)
+                .and_then(|d| u8::try_from(d).ok())
+                .map(Control::Rate),
+            KeyCode::Char('q') | KeyCode::Esc => Some(Control::Quit),
+            _ => None,
+        }
+    }
+}
+
+type SinkFactory = Box<dyn Fn() -> Result<rodio::Sink, CriticalErrorKind>>;
+
+/// Queue of musics played one at a time, each music getting its own sink
+pub struct Player {
+    new_sink: SinkFactory,
+    sink: Option<rodio::Sink>,
+    queue: Vec<MusicResult>,
+    position: usize,
+}
+
+impl Player {
+    #[must_use]
+    pub fn new(new_sink: SinkFactory, queue: Vec<MusicResult>) -> Self {
+        Self {
+            new_sink,
+            sink: None,
+            queue,
+            position: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn current(&self) -> Option<&MusicResult> {
+        self.queue.get(self.position)
+    }
+
+    #[must_use]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Start the current music, skipping the ones which can not be played, false at the end of the queue
+    pub fn load(&mut self) -> Result<bool, CriticalErrorKind> {
+        self.sink = None;
+        while let Some(music) = self.queue.get(self.position) {
+            let decoder = local_source(music)
+                .ok_or_else(|| "no local file found".to_string())
+                .and_then(|path| std::fs::File::open(path).map_err(|e| e.to_string()))
+                .and_then(|file| {
+                    rodio::Decoder::new(BufReader::new(file)).map_err(|e| e.to_string())
+                });
+            match decoder {
+                Ok(decoder) => {
+                    let sink = (self.new_sink)()?;
+                    sink.append(decoder);
+                    sink.play();
+                    self.sink = Some(sink);
+                    return Ok(true);
+                }
+                Err(e) => eprint!("{music} : {e}, skipping\r\n"),
+            }
+            self.position += 1;
+        }
+        Ok(false)
+    }
+
+    pub fn play_next(&mut self) -> Result<bool, CriticalErrorKind> {
+        self.position = (self.position + 1).min(self.queue.len());
+        self.load()
+    }
+
+    pub fn play_previous(&mut self) -> Result<bool, CriticalErrorKind> {
+        self.position = self.position.saturating_sub(1);
+        self.load()
+    }
+
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.sink.as_ref().is_some_and(rodio::Sink::is_paused)
+    }
+
+    pub fn toggle_pause(&self) {
+        if let Some(sink) = &self.sink {
+            if sink.is_paused() {
+                sink.play();
+            } else {
+                sink.pause();
+            }
+        }
+    }
+
+    pub fn seek(&self, forward: bool) -> Result<(), CriticalErrorKind> {
+        if let Some(sink) = &self.sink {
+            let position = if forward {
+                sink.get_pos() + SEEK_STEP
+            } else {
+                sink.get_pos().saturating_sub(SEEK_STEP)
+            };
+            sink.try_seek(position)?;
+        }
+        Ok(())
+    }
+
+    /// True once the current music has been played
+    #[must_use]
+    pub fn finished(&self) -> bool {
+        self.sink.as_ref().is_none_or(rodio::Sink::empty)
+    }
+
+    /// Rate the current music and write the rating to its local file
+    pub fn rate(
+        &mut self,
+        rating: Rating,
+        dry: bool,
+    ) -> Result<Option<&MusicResult>, CriticalErrorKind> {
+        let Some(music) = self.queue.get_mut(self.position) else {
+            return Ok(None);
+        };
+        if !dry && let Some(folder) = music.local_folders().next() {
+            let mut music_file = open_music_file(&folder.name, &folder.path)?;
+            music_file.set_rating(rating);
+            music_file.save()?;
+        }
+        music.rating = rating.into();
+        Ok(Some(music))
+    }
+}
+
+impl Play {
+    pub async fn play(&self, config: Config) -> Result<(), CriticalErrorKind> {
+        let playlist = Box::pin(Playlist::from_filters(
+            &config.gel,
+            PLAY_NAME,
+            &self.filters,
+        ))
+        .await?;
+        let musics = playlist.ordered_musics(&self.playlist_options)?;
+
+        // The audio output can not be held across await points, playback runs on its own thread
+        // and sends each rating to be stored as soon as it is made
+        let (rated, mut ratings) = tokio::sync::mpsc::unbounded_channel();
+        let dry = config.dry;
+        let player = tokio::task::spawn_blocking(move || Self::run(musics, dry, &rated));
+        let mut saved = Ok(());
+        while let Some(music) = ratings.recv().await {
+            if saved.is_ok() {
+                saved = Box::pin(Self::save_rating(&config, &music)).await;
+            }
+        }
+        player.await??;
+        saved
+    }
+
+    async fn save_rating(config: &Config, music: &MusicResult) -> Result<(), CriticalErrorKind> {
+        let Some(folder) = music.folders.first() else {
+            return Ok(());
+        };
+        if config.dry {
+            eprint!("Rate {} : {}\r\n", folder.path, music.rating);
+            return Ok(());
+        }
+        Box::pin(
+            config
+                .gel
+                .execute(UPDATE_RATING_QUERY, &(&folder.path, music.rating)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Play musics until the end of the queue or until asked to quit, sending rated musics
+    fn run(
+        musics: Vec<MusicResult>,
+        dry: bool,
+        rated: &UnboundedSender<MusicResult>,
+    ) -> Result<(), CriticalErrorKind> {
+        let (_stream, handle) = rodio::OutputStream::try_default()?;
+        let mut player = Player::new(Box::new(move || Ok(rodio::Sink::try_new(&handle)?)), musics);
+
+        println!("{CONTROLS}");
+        terminal::enable_raw_mode()?;
+        scopeguard::defer! {
+            let _ = terminal::disable_raw_mode();
+        };
+
+        let mut playing = player.load()?;
+        while playing {
+            if let Some(music) = player.current() {
+                print!("Now playing : {}\r\n", music.now_playing());
+            }
+            loop {
+                let control = if event::poll(POLL_INTERVAL)?
+                    && let Event::Key(key) = event::read()?
+                    && key.kind == KeyEventKind::Press
+                {
+                    Control::from_key(key.code, key.modifiers)
+                } else {
+                    None
+                };
+                match control {
+                    Some(Control::Quit) => return Ok(()),
+                    Some(Control::Next) => {
+                        playing = player.play_next()?;
+                        break;
+                    }
+                    Some(Control::Previous) => {
+                        playing = player.play_previous()?;
+                        break;
+                    }
+                    Some(Control::TogglePause) => player.toggle_pause(),
+                    Some(Control::SeekForward) => player.seek(true)?,
+                    Some(Control::SeekBackward) => player.seek(false)?,
+                    Some(Control::Rate(stars)) => {
+                        let rating = Rating::try_from(f64::from(stars))?;
+                        if let Some(music) = player.rate(rating, dry)? {
+                            print!("Rated : {}\r\n", music.now_playing());
+                            // The receiver lives until playback ends
+                            let _ = rated.send(music.clone());
+                        }
+                    }
+                    None if player.finished() => {
+                        playing = player.play_next()?;
+                        break;
+                    }
+                    None => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn player_tests() {
+    use crate::test_helpers::{music, wav};
+
+    let folder = tempfile::tempdir().unwrap();
+    let music = |name: &str, exists: bool| {
+        let path = folder.path().join(format!("{name}.wav"));
+        if exists {
+            // One second of 8kHz mono silence
+            std::fs::write(&path, wav(1, 8000, &[0; 8000])).unwrap();
+        }
+        music(
+            name,
+            &folder.path().display().to_string(),
+            &path.display().to_string(),
+        )
+    };
+    let queue = vec![
+        music("first", true),
+        music("missing", false),
+        music("last", true),
+    ];
+    let mut player = Player::new(Box::new(|| Ok(rodio::Sink::new_idle().0)), queue);
+
+    assert!(player.finished());
+    assert!(player.load().unwrap());
+    assert_eq!(player.current().unwrap().name, "first");
+    assert!(!player.finished());
+    assert!(
+        player
+            .current()
+            .unwrap()
+            .now_playing()
+            .starts_with("Artist - Album - 01 first")
+    );
+
+    player.toggle_pause();
+    assert!(player.is_paused());
+    player.toggle_pause();
+    assert!(!player.is_paused());
+
+    assert!(player.play_next().unwrap());
+    assert_eq!(player.current().unwrap().name, "last");
+    assert!(player.play_previous().unwrap());
+    assert_eq!(player.position(), 2);
+    assert!(!player.play_next().unwrap());
+    assert!(player.current().is_none());
+
+    assert_eq!(
+        Control::from_key(KeyCode::Char('4'), KeyModifiers::NONE),
+        Some(Control::Rate(4))
+    );
+    assert_eq!(
+        Control::from_key(KeyCode::Char('c'), KeyModifiers::CONTROL),
+        Some(Control::Quit)
+    );
+    assert_eq!(
+        Control::from_key(KeyCode::Char('6'), KeyModifiers::NONE),
+        None
+    );
+}
@@ -7,8 +7,11 @@ use std::fmt::Write;
 use tabled::Table;
 
 use super::errors::CriticalErrorKind;
+use super::export::Export;
 use super::filter::Filters;
 use super::helpers::interleave_evenly;
+use super::links::LinkOptions;
+use super::mpd::MpdOptions;
 use super::music::MUSIC_FIELDS;
 use super::music_result::MusicResult;
 
@@ -21,12 +24,14 @@ pub enum Output {
     M3u,
     Json,
     Table,
+    Mpd,
 }
 
 #[derive(clap::ValueEnum, Clone, Debug, Serialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub enum Kind {
     Local,
+    File,
     Remote,
     RemoteSSH,
     LocalSSH,
@@ -53,6 +58,14 @@ pub struct PlaylistCommand {
     /// More filters
     #[clap(flatten)]
     filters: Filters,
+
+    #[clap(subcommand)]
+    action: Option<PlaylistAction>,
+}
+
+#[derive(clap::Subcommand, Clone)]
+pub enum PlaylistAction {
+    Export(Export),
 }
 
 #[derive(clap::Parser, Default, Clone)]
@@ -64,6 +77,10 @@ pub struct OutputOptions {
     /// Optional output path
     #[clap(long)]
     out: Option<String>,
+
+    /// MPD options
+    #[clap(flatten)]
+    mpd_options: MpdOptions,
 }
 
 impl OutputOptions {
@@ -72,6 +89,7 @@ impl OutputOptions {
         Self {
             output: output.clone(),
             out: out.clone(),
+            mpd_options: MpdOptions::default(),
         }
     }
     #[must_use]
@@ -86,11 +104,9 @@ impl OutputOptions {
 
 #[derive(clap::Parser, Default)]
 pub struct PlaylistOptions {
-    #[clap(long, value_enum)]
-    kind: Vec<Kind>,
-
-    #[clap(long)]
-    relative: bool,
+    /// Link options
+    #[clap(flatten)]
+    link_options: LinkOptions,
 
     #[clap(long, group = "order")]
     interleave: bool,
@@ -99,12 +115,19 @@ pub struct PlaylistOptions {
     shuffle: bool,
 }
 
-#[derive(Queryable, Clone)]
+#[derive(Queryable, Serialize, Clone)]
 pub struct Playlist {
     name: String,
     musics: Vec<MusicResult>,
 }
 
+impl PlaylistOptions {
+    #[must_use]
+    pub fn link_options(&self) -> &LinkOptions {
+        &self.link_options
+    }
+}
+
 impl Playlist {
     #[must_use]
     pub fn new(name: &str, musics: &[MusicResult]) -> Self {
@@ -113,6 +136,22 @@ impl Playlist {
             musics: musics.to_vec(),
         }
     }
+    /// Musics matching any of the filters
+    pub async fn from_filters(
+        client: &gel_tokio::Client,
+        name: &str,
+        filters: &Filters,
+    ) -> Result<Self, CriticalErrorKind> {
+        let mut musics: HashSet<MusicResult> = HashSet::new();
+        for filter in &filters.all() {
+            let music_filter = serde_json::to_string(filter)?;
+            let music_results: Vec<MusicResult> =
+                Box::pin(client.query(PLAYLIST_QUERY, &(music_filter,))).await?;
+            musics.extend(music_results);
+        }
+        let musics = musics.into_iter().collect::<Vec<MusicResult>>();
+        Ok(Playlist::new(name, &musics))
+    }
     #[must_use]
     pub fn name(&self) -> &str {
         &self.name
@@ -125,12 +164,11 @@ impl Playlist {
     pub fn is_empty(&self) -> bool {
         self.musics.is_empty()
     }
-    pub fn generate(
+    /// Musics shuffled or interleaved according to playlist options
+    pub fn ordered_musics(
         &self,
-        output_options: &OutputOptions,
         playlist_options: &PlaylistOptions,
-        dry: bool,
-    ) -> Result<(), CriticalErrorKind> {
+    ) -> Result<Vec<MusicResult>, CriticalErrorKind> {
         let mut musics = self.musics.clone();
         if playlist_options.shuffle {
             let mut rng = rng();
@@ -149,12 +187,40 @@ impl Playlist {
                 .collect::<Vec<Vec<MusicResult>>>();
             musics = interleave_evenly(values)?;
         }
+        Ok(musics)
+    }
 
-        let kind = if playlist_options.kind.is_empty() {
-            vec![Kind::Local]
-        } else {
-            playlist_options.kind.clone()
-        };
+    pub fn m3u(
+        &self,
+        musics: &[MusicResult],
+        link_options: &LinkOptions,
+        out: Option<&str>,
+    ) -> Result<String, CriticalErrorKind> {
+        let mut playlist = "#EXTM3U\n".to_string();
+
+        writeln!(playlist, "#EXTREM:name={}", self.name)?;
+        if let Some(out) = out {
+            writeln!(playlist, "#EXTREM:path={out}")?;
+        }
+
+        let mut links = Vec::new();
+        for music in musics {
+            links.extend_from_slice(music.all_links(link_options)?.as_slice());
+        }
+
+        for link in links {
+            writeln!(playlist, "{link}")?;
+        }
+        Ok(playlist)
+    }
+
+    pub fn generate(
+        &self,
+        output_options: &OutputOptions,
+        playlist_options: &PlaylistOptions,
+        dry: bool,
+    ) -> Result<(), CriticalErrorKind> {
+        let musics = self.ordered_musics(playlist_options)?;
 
         if musics.is_empty() && output_options.output != Output::Json {
             return Ok(());
@@ -162,34 +228,18 @@ impl Playlist {
 
         let playlist = match output_options.output {
             Output::M3u => {
-                let mut playlist = "#EXTM3U\n".to_string();
-
-                writeln!(playlist, "#EXTREM:name={}", self.name)?;
-                if let Some(out) = &output_options.out {
-                    writeln!(playlist, "#EXTREM:path={out}")?;
-                }
-
-                let mut links = Vec::new();
-                for music in musics {
-                    links.extend_from_slice(
-                        music
-                            .all_links(playlist_options.relative, &kind)?
-                            .as_slice(),
-                    );
-                }
-
-                let links = links.join("\n");
-                playlist.push_str(&links);
-                playlist
+                let link_options = playlist_options
+                    .link_options
+                    .for_playlist(output_options.out.as_deref())?;
+                self.m3u(&musics, &link_options, output_options.out.as_deref())?
             }
             Output::Table => Table::new(musics).to_string(),
+            Output::Mpd => return output_options.mpd_options.push(&musics, dry),
             Output::Json => serde_json::to_string_pretty(&musics)?,
         };
-        if !dry {
-            if let Some(out) = &output_options.out {
-                std::fs::write(out, playlist)?;
-                return Ok(());
-            }
+        if !dry && let Some(out) = &output_options.out {
+            std::fs::write(out, playlist)?;
+            return Ok(());
         }
         print!("{playlist}");
         Ok(())
@@ -198,15 +248,7 @@ impl Playlist {
 
 impl PlaylistCommand {
     pub async fn playlist(&self, client: gel_tokio::Client) -> Result<Playlist, CriticalErrorKind> {
-        let mut musics: HashSet<MusicResult> = HashSet::new();
-        for filter in &self.filters.all() {
-            let music_filter = serde_json::to_string(filter)?;
-            let music_results: Vec<MusicResult> =
-                Box::pin(client.query(PLAYLIST_QUERY, &(music_filter,))).await?;
-            musics.extend(music_results);
-        }
-        let musics = musics.into_iter().collect::<Vec<MusicResult>>();
-        Ok(Playlist::new(&self.name, &musics))
+        Box::pin(Playlist::from_filters(&client, &self.name, &self.filters)).await
     }
     #[must_use]
     pub fn output_options(&self) -> &OutputOptions {
@@ -216,10 +258,14 @@ impl PlaylistCommand {
     pub fn playlist_options(&self) -> &PlaylistOptions {
         &self.playlist_options
     }
+    #[must_use]
+    pub fn action(&self) -> Option<&PlaylistAction> {
+        self.action.as_ref()
+    }
 }
 
-pub const PLAYLIST_QUERY: &str = concatcp!(
-    "
+/// Musics matching a JSON filter, to be followed by a shape
+pub const GEN_PLAYLIST: &str = "
     with music_filter := to_json(<str>$0),
     select gen_playlist(
         min_length := <Length>music_filter['min_length'],
@@ -234,8 +280,14 @@ pub const PLAYLIST_QUERY: &str = concatcp!(
         title := <str>music_filter['title'],
         keyword := <str>music_filter['keyword'],
         pattern := <str>music_filter['pattern'],
+        integrity := <str>music_filter['integrity'],
+        spectrum := <str>music_filter['spectrum'],
         limit := <`Limit`>music_filter['limit']
-    ) {
+    )";
+
+pub const PLAYLIST_QUERY: &str = concatcp!(
+    GEN_PLAYLIST,
+    " {
         ",
     MUSIC_FIELDS,
     r"
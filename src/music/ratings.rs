@@ -4,7 +4,7 @@ use super::errors::CriticalErrorKind;
 
 pub const RATINGS: &[f64] = &[0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 3.5, 4.0, 4.5, 5.0];
 
-#[derive(Default, Hash, Serialize, Debug, Clone, Copy)]
+#[derive(Default, Hash, Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum Rating {
     #[default]
     Zero,
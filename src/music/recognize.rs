@@ -0,0 +1,426 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::config::Config;
+use super::errors::CriticalErrorKind;
+use super::filter::Filters;
+use super::folders::local_files;
+use super::helpers::parse_duration;
+use super::shazam::{SongRecognizedMessage, parse_song};
+use crate::fingerprinting::algorithm::SignatureGenerator;
+use crate::fingerprinting::communication::{
+    DEFAULT_LOCALE, DEFAULT_SHAZAM_ENDPOINT, DEFAULT_TIMEOUT, DEFAULT_TIMEZONE, Recognizer,
+    ReplayRecognizer, ShazamRecognizer,
+};
+use crate::fingerprinting::signature_format::DecodedSignature;
+
+const DEFAULT_INTERVAL_MS: u64 = 3000;
+const DEFAULT_RETRIES: u32 = 3;
+const BACKOFF_MS: u64 = 1000;
+
+#[derive(clap::Parser)]
+pub struct RecognizerOptions {
+    /// Shazam recognition endpoint
+    #[clap(long, default_value = DEFAULT_SHAZAM_ENDPOINT)]
+    endpoint: String,
+
+    /// Minimum delay between two requests, in milliseconds
+    #[clap(long, default_value_t = DEFAULT_INTERVAL_MS)]
+    interval: u64,
+
+    /// Retries of a failed request, with a jittered exponential backoff
+    #[clap(long, default_value_t = DEFAULT_RETRIES)]
+    retries: u32,
+
+    /// Locale of the Shazam client
+    #[clap(long, default_value = DEFAULT_LOCALE)]
+    locale: String,
+
+    /// Timezone of the Shazam client
+    #[clap(long, default_value = DEFAULT_TIMEZONE)]
+    timezone: String,
+
+    /// Timeout of a request
+    #[clap(long, default_value = DEFAULT_TIMEOUT, value_parser = parse_duration)]
+    timeout: Duration,
+
+    /// Proxy URL for requests
+    #[clap(long)]
+    proxy: Option<String>,
+
+    /// Replay responses recorded in a JSON file instead of requesting Shazam
+    #[clap(long)]
+    replay: Option<String>,
+}
+
+impl RecognizerOptions {
+    pub fn recognizer(&self) -> Result<PacedRecognizer, CriticalErrorKind> {
+        if let Some(replay) = &self.replay {
+            return Ok(PacedRecognizer::new(
+                Arc::new(ReplayRecognizer::new(replay)?),
+                Duration::ZERO,
+                0,
+                Duration::ZERO,
+            ));
+        }
+        Ok(PacedRecognizer::new(
+            Arc::new(ShazamRecognizer::new(
+                &self.endpoint,
+                &self.locale,
+                &self.timezone,
+                self.timeout,
+                self.proxy.as_deref(),
+            )?),
+            Duration::from_millis(self.interval),
+            self.retries,
+            Duration::from_millis(BACKOFF_MS),
+        ))
+    }
+}
+
+#[derive(clap::Parser)]
+#[clap(about = "Recognize musics with Shazam and store results")]
+pub struct Recognize {
+    /// Recognition options
+    #[clap(flatten)]
+    recognizer_options: RecognizerOptions,
+
+    /// Recognize again musics already recognized
+    #[clap(long)]
+    force: bool,
+
+    /// Filters selecting musics, when no folder is given
+    #[clap(flatten)]
+    filters: Filters,
+
+    /// Folders to recognize instead of filtered musics
+    folders: Vec<String>,
+}
+
+/// Paced requests to a recognizer, retrying failures, clones sharing the same pace
+#[derive(Clone)]
+pub struct PacedRecognizer {
+    recognizer: Arc<dyn Recognizer>,
+    interval: Duration,
+    retries: u32,
+    backoff: Duration,
+    last_request: Arc<tokio::sync::Mutex<Option<Instant>>>,
+}
+
+impl PacedRecognizer {
+    #[must_use]
+    pub fn new(
+        recognizer: Arc<dyn Recognizer>,
+        interval: Duration,
+        retries: u32,
+        backoff: Duration,
+    ) -> Self {
+        Self {
+            recognizer,
+            interval,
+            retries,
+            backoff,
+            last_request: Arc::default(),
+        }
+    }
+
+    /// Wait for the interval since the previous request, the lock being held for concurrent
+    /// requests to wait in turn
+    async fn wait(&self) {
+        let mut last_request = self.last_request.lock().await;
+        if let Some(previous) = *last_request
+            && let Some(remaining) = self.interval.checked_sub(previous.elapsed())
+        {
+            tokio::time::sleep(remaining).await;
+        }
+        *last_request = Some(Instant::now());
+    }
+
+    /// Backoff doubling at each attempt, randomized up to twice as long to spread clients
+    fn backoff(&self, attempt: u32) -> Duration {
+        let jitter = 1.0 + rand::random::<f64>();
+        self.backoff
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .mul_f64(jitter)
+    }
+
+    /// Raw Shazam response of a signature
+    pub async fn recognize(
+        &self,
+        signature: &DecodedSignature,
+    ) -> Result<serde_json::Value, CriticalErrorKind> {
+        let mut attempt = 0;
+        loop {
+            self.wait().await;
+            match self.recognizer.recognize(signature).await {
+                Ok(response) => return Ok(response),
+                Err(CriticalErrorKind::ReqwestError(e))
+                    if attempt < self.retries && transient(&e) =>
+                {
+                    let backoff = self.backoff(attempt);
+                    eprintln!("{e}, retrying in {}ms", backoff.as_millis());
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Failures worth retrying, client errors other than rate limiting failing the same way again
+fn transient(error: &reqwest::Error) -> bool {
+    error.is_timeout()
+        || error.is_connect()
+        || error.status().is_some_and(|status| {
+            status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+        })
+}
+
+impl Recognize {
+    pub async fn recognize(&self, config: Config) -> Result<(), CriticalErrorKind> {
+        let files = Box::pin(local_files(&config, &self.folders, &self.filters)).await?;
+        let recognized: HashSet<String> = if self.force {
+            HashSet::new()
+        } else {
+            Box::pin(config.gel.query(RECOGNIZED_PATHS_QUERY, &()))
+                .await?
+                .into_iter()
+                .collect()
+        };
+        let paths = files
+            .into_iter()
+            .map(|(_, path)| path)
+            .filter(|path| !recognized.contains(path))
+            .collect::<Vec<_>>();
+
+        let recognize_bar = indicatif::ProgressBar::new(paths.len() as u64);
+        recognize_bar.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template(
+                    "[{elapsed_precise}] Recognizing files: {bar:50.cyan/blue} {pos:>7}/{len:7} {msg}",
+                )?
+                .progress_chars("##-"),
+        );
+
+        let recognizer = self.recognizer_options.recognizer()?;
+        let (mut recognized, mut unmatched, mut failed) = (0, 0, 0);
+        for path in paths {
+            scopeguard::defer! {recognize_bar.inc(1)};
+            let signature =
+                match SignatureGenerator::make_signature_from_file(&path, config.ffmpeg.as_deref())
+                {
+                    Ok(signature) => signature,
+                    Err(e) => {
+                        recognize_bar.println(format!("{path} : {e}"));
+                        failed += 1;
+                        continue;
+                    }
+                };
+            let response = match recognizer.recognize(&signature).await {
+                Ok(response) => response,
+                Err(e) => {
+                    recognize_bar.println(format!("{path} : {e}"));
+                    failed += 1;
+                    continue;
+                }
+            };
+            let song = match parse_song(path.clone(), &response) {
+                Ok(song) => song,
+                Err(e) => {
+                    if matches!(e, CriticalErrorKind::NoMatch { .. }) {
+                        unmatched += 1;
+                    } else {
+                        failed += 1;
+                    }
+                    recognize_bar.println(e.to_string());
+                    continue;
+                }
+            };
+            recognized += 1;
+            recognize_bar.println(format!(
+                "{path} : {} - {} - {}",
+                song.artist_name,
+                song.album_name.as_deref().unwrap_or("Unknown"),
+                song.song_name
+            ));
+            if !config.dry {
+                Box::pin(store(&config, &song, &response)).await?;
+            }
+        }
+        recognize_bar.finish();
+        eprintln!("{recognized} recognized, {unmatched} not matched, {failed} failed");
+        Ok(())
+    }
+}
+
+async fn store(
+    config: &Config,
+    song: &SongRecognizedMessage,
+    response: &serde_json::Value,
+) -> Result<(), CriticalErrorKind> {
+    let musics: Vec<uuid::Uuid> = Box::pin(config.gel.query(
+        STORE_RECOGNITION_QUERY,
+        &(
+            &song.path,
+            &song.artist_name,
+            &song.song_name,
+            song.album_name.as_deref(),
+            gel_protocol::model::Json::new_unchecked(response.to_string()),
+        ),
+    ))
+    .await?;
+    if musics.is_empty() {
+        eprintln!("{} : not in library, run a scan first", song.path);
+    }
+    Ok(())
+}
+
+const RECOGNIZED_PATHS_QUERY: &str = r"
+select (select Music filter exists .shazam_response).folders@path
+";
+
+const STORE_RECOGNITION_QUERY: &str = r"
+select (
+    update Music
+    filter <str>$0 in .folders@path
+    set {
+        shazam_artist := <str>$1,
+        shazam_title := <str>$2,
+        shazam_album := <optional str>$3,
+        shazam_response := <json>$4
+    }
+).id
+";
+
+#[tokio::test]
+async fn recognizer_tests() {
+    use axum::Router;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Mock endpoint, rate limiting the first request, and one rejecting every request
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mock_calls = calls.clone();
+    let rejected_calls = calls.clone();
+    let app = Router::new().route(
+        "/bad/{uuid_1}/{uuid_2}",
+        post(move || {
+            rejected_calls.fetch_add(1, Ordering::SeqCst);
+            async { StatusCode::BAD_REQUEST }
+        }),
+    );
+    let app = app.route(
+        "/tag/{uuid_1}/{uuid_2}",
+        post(move || {
+            let calls = mock_calls.clone();
+            async move {
+                if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return (StatusCode::TOO_MANY_REQUESTS, String::new());
+                }
+                (
+                    StatusCode::OK,
+                    serde_json::json!({
+                        "track": {
+                            "title": "Thunderstruck",
+                            "subtitle": "AC/DC",
+                            "sections": [{
+                                "type": "SONG",
+                                "metadata": [{"title": "Album", "text": "The Razors Edge"}]
+                            }]
+                        }
+                    })
+                    .to_string(),
+                )
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let endpoint = format!("http://{address}/tag");
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let signature = SignatureGenerator::make_signature_from_buffer(&vec![0; 16000]);
+    let shazam = |timeout| -> Arc<dyn Recognizer> {
+        Arc::new(ShazamRecognizer::new(&endpoint, "fr-FR", "Europe/Paris", timeout, None).unwrap())
+    };
+    let recognizer = PacedRecognizer::new(
+        shazam(Duration::from_secs(5)),
+        Duration::from_millis(10),
+        2,
+        Duration::from_millis(10),
+    );
+    let response = recognizer.recognize(&signature).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    let song = parse_song("a.flac".to_string(), &response).unwrap();
+    assert_eq!(song.artist_name, "AC/DC");
+    assert_eq!(song.song_name, "Thunderstruck");
+    assert_eq!(song.album_name.as_deref(), Some("The Razors Edge"));
+    assert!(parse_song("a.flac".to_string(), &serde_json::json!({"matches": []})).is_err());
+
+    let recognizer = PacedRecognizer::new(
+        shazam(Duration::from_secs(5)),
+        Duration::ZERO,
+        0,
+        Duration::ZERO,
+    );
+    calls.store(0, Ordering::SeqCst);
+    assert!(recognizer.recognize(&signature).await.is_err());
+
+    // Clones share the pace, concurrent requests waiting in turn
+    let recognizer = PacedRecognizer::new(
+        shazam(Duration::from_secs(5)),
+        Duration::from_millis(100),
+        0,
+        Duration::ZERO,
+    );
+    let (first, second, third) = (recognizer.clone(), recognizer.clone(), recognizer);
+    calls.store(1, Ordering::SeqCst);
+    let start = Instant::now();
+    let (first, second, third) = tokio::join!(
+        first.recognize(&signature),
+        second.recognize(&signature),
+        third.recognize(&signature)
+    );
+    assert!(first.is_ok() && second.is_ok() && third.is_ok());
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // Client errors are not retried
+    let rejecting = Arc::new(
+        ShazamRecognizer::new(
+            &format!("http://{address}/bad"),
+            "fr-FR",
+            "Europe/Paris",
+            Duration::from_secs(5),
+            None,
+        )
+        .unwrap(),
+    );
+    let recognizer = PacedRecognizer::new(rejecting, Duration::ZERO, 2, Duration::ZERO);
+    calls.store(0, Ordering::SeqCst);
+    assert!(recognizer.recognize(&signature).await.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Recorded responses are replayed offline
+    let responses = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(responses.path(), response.to_string()).unwrap();
+    let options = <RecognizerOptions as clap::Parser>::try_parse_from([
+        "recognize",
+        "--replay",
+        responses.path().to_str().unwrap(),
+    ])
+    .unwrap();
+    let recognizer = options.recognizer().unwrap();
+    let song = parse_song(
+        "a.flac".to_string(),
+        &recognizer.recognize(&signature).await.unwrap(),
+    );
+    assert_eq!(song.unwrap().song_name, "Thunderstruck");
+    assert!(matches!(
+        recognizer.recognize(&signature).await,
+        Err(CriticalErrorKind::ReplayExhausted { .. })
+    ));
+}
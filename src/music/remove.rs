@@ -7,6 +7,13 @@ pub struct Remove {
 }
 
 impl Remove {
+    #[must_use]
+    pub fn new(paths: &[String]) -> Self {
+        Self {
+            paths: paths.to_vec(),
+        }
+    }
+
     pub async fn remove(
         &self,
         client: gel_tokio::Client,
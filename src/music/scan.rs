@@ -40,6 +40,16 @@ pub struct Scan {
 }
 
 impl Scan {
+    #[must_use]
+    pub fn new(folders: &[String], clean: bool) -> Self {
+        Self {
+            clean,
+            retries: DEFAULT_RETRIES,
+            max_files: 0,
+            folders: folders.to_vec(),
+        }
+    }
+
     pub async fn scan(&self, mut config: Config) -> Result<(), CriticalErrorKind> {
         if self.clean && !config.no_gel {
             Box::pin(clean(&config.gel, false, config.dry)).await?;
@@ -47,7 +57,7 @@ impl Scan {
 
         config.retries = self.retries.into();
         let ipv4 = public_ip().await?;
-        let username = whoami::username().to_string();
+        let username = whoami::username().clone();
         let mut cache = UpsertCache::default();
         let mut count: u64 = 0;
         let mut paths = HashMap::<String, Vec<PathBuf>>::new();
@@ -1,61 +1,479 @@
+use super::config::Config;
 use super::errors::CriticalErrorKind;
-use crate::fingerprinting::communication::recognize_song_from_signature;
-use crate::fingerprinting::signature_format::DecodedSignature;
+use super::helpers::{ReportOutput, parse_duration};
+use super::recognize::RecognizerOptions;
+use super::tracklist::{Tracklist, TracklistFormat, timestamp};
+use super::vote::{Consensus, window_signatures};
+use crate::fingerprinting::algorithm::{SAMPLE_RATE, SignatureGenerator, duration_of, samples_of};
+use crate::fingerprinting::pcm::PcmSource;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::time::Duration;
+
+const DEFAULT_WINDOW: &str = "12s";
+const DEFAULT_STEP: &str = "30s";
+const DEFAULT_DURATION: &str = "12s";
+const STDIN: &str = "-";
+/// Samples converted to 16 KHz mono before being fed to the signature generator
+const FEED_LEN: usize = 4096;
 
 #[derive(clap::Parser)]
 #[clap(about = "Detect song")]
 pub struct Shazam {
+    /// Audio file, or - to read WAV or raw s16le PCM from stdin
     pub file: String,
+
+    /// Recognition options
+    #[clap(flatten)]
+    pub recognizer_options: RecognizerOptions,
+
+    /// Start of the recognized window, the middle of the file by default
+    #[clap(long, value_parser = parse_duration)]
+    pub offset: Option<Duration>,
+
+    /// Duration of the recognized window
+    #[clap(long, default_value = DEFAULT_DURATION, value_parser = parse_duration)]
+    pub duration: Duration,
+
+    /// Windows at evenly spaced positions voting for the song, when no offset is given
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub windows: u16,
+
+    /// Recognize voting windows concurrently
+    #[clap(long)]
+    pub parallel: bool,
+
+    /// Recognize windows sliding over the whole file, building a tracklist
+    #[clap(long)]
+    pub segments: bool,
+
+    /// Duration of each window
+    #[clap(long, default_value = DEFAULT_WINDOW, value_parser = parse_duration)]
+    pub window: Duration,
+
+    /// Delay between the start of two windows
+    #[clap(long, default_value = DEFAULT_STEP, value_parser = parse_duration)]
+    pub step: Duration,
+
+    /// Tracklist format
+    #[clap(long, value_enum, default_value_t)]
+    pub format: TracklistFormat,
+
+    /// Recognized song format
+    #[clap(long, value_enum, default_value_t)]
+    pub output: ReportOutput,
+
+    /// Keep reading stdin, recognizing a signature of each window
+    #[clap(long)]
+    pub rolling: bool,
+
+    /// Sample rate of raw PCM read from stdin
+    #[clap(long, default_value_t = 16000)]
+    pub rate: u32,
+
+    /// Channels of raw PCM read from stdin
+    #[clap(long, default_value_t = 1)]
+    pub channels: u16,
+}
+
+impl Shazam {
+    pub async fn shazam(&self, config: &Config) -> Result<(), CriticalErrorKind> {
+        let ffmpeg = config.ffmpeg.as_deref();
+        if self.file == STDIN {
+            return Box::pin(self.stream()).await;
+        }
+        if self.segments {
+            return Box::pin(self.tracklist(ffmpeg)).await;
+        }
+        if self.windows > 1 && self.offset.is_none() {
+            return Box::pin(self.vote(ffmpeg)).await;
+        }
+        let signature = SignatureGenerator::make_signature_from_window(
+            &self.file,
+            ffmpeg,
+            self.offset,
+            self.duration,
+        )?;
+        let response = self
+            .recognizer_options
+            .recognizer()?
+            .recognize(&signature)
+            .await?;
+        self.print_song(&parse_song(self.file.clone(), &response)?)
+    }
+
+    fn print_song(&self, song: &SongRecognizedMessage) -> Result<(), CriticalErrorKind> {
+        match self.output {
+            ReportOutput::Table => println!("{song}"),
+            ReportOutput::Json => println!("{}", serde_json::to_string_pretty(song)?),
+        }
+        Ok(())
+    }
+
+    /// Recognize several windows, printing the song most of them agree on
+    async fn vote(&self, ffmpeg: Option<&str>) -> Result<(), CriticalErrorKind> {
+        let signatures =
+            window_signatures(&self.file, ffmpeg, usize::from(self.windows), self.duration)?;
+        let mut recognitions = Vec::new();
+        if self.parallel {
+            let recognizer = self.recognizer_options.recognizer()?;
+            let mut tasks = tokio::task::JoinSet::new();
+            for (offset, signature) in signatures {
+                let recognizer = recognizer.clone();
+                let path = self.file.clone();
+                tasks.spawn(async move {
+                    let song = match recognizer.recognize(&signature).await {
+                        Ok(response) => parse_song(path, &response),
+                        Err(e) => Err(e),
+                    };
+                    (offset, song)
+                });
+            }
+            while let Some(recognition) = tasks.join_next().await {
+                recognitions.push(recognition?);
+            }
+        } else {
+            let recognizer = self.recognizer_options.recognizer()?;
+            for (offset, signature) in signatures {
+                let song = match recognizer.recognize(&signature).await {
+                    Ok(response) => parse_song(self.file.clone(), &response),
+                    Err(e) => Err(e),
+                };
+                recognitions.push((offset, song));
+            }
+        }
+
+        let consensus = Consensus::new(&self.file, recognitions)?;
+        match self.output {
+            ReportOutput::Table => println!("{consensus}"),
+            ReportOutput::Json => println!("{}", serde_json::to_string_pretty(&consensus)?),
+        }
+        Ok(())
+    }
+
+    async fn tracklist(&self, ffmpeg: Option<&str>) -> Result<(), CriticalErrorKind> {
+        let samples = SignatureGenerator::decode_file(&self.file, ffmpeg)?;
+        let signatures = SignatureGenerator::make_signatures_from_buffer(
+            &samples,
+            samples_of(self.window),
+            samples_of(self.step),
+        );
+
+        let segments_bar = indicatif::ProgressBar::new(signatures.len() as u64);
+        segments_bar.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template(
+                    "[{elapsed_precise}] Recognizing segments: {bar:50.cyan/blue} {pos:>7}/{len:7} {msg}",
+                )?
+                .progress_chars("##-"),
+        );
+
+        let recognizer = self.recognizer_options.recognizer()?;
+        let mut tracklist = Tracklist::default();
+        for (offset, signature) in signatures {
+            scopeguard::defer! {segments_bar.inc(1)};
+            let start = duration_of(offset);
+            let end = duration_of(offset + signature.number_samples as usize);
+            let song = match recognizer.recognize(&signature).await {
+                Ok(response) => parse_song(self.file.clone(), &response),
+                Err(e) => Err(e),
+            };
+            match song {
+                Ok(song) => tracklist.push(start, end, song),
+                Err(CriticalErrorKind::NoMatch { .. }) => {}
+                Err(e) => segments_bar.println(format!("{start:?} : {e}")),
+            }
+        }
+        segments_bar.finish_and_clear();
+        print!("{}", tracklist.render(&self.format, &self.file)?);
+        Ok(())
+    }
+
+    /// Recognize the first window read from stdin, or each of them in rolling mode
+    async fn stream(&self) -> Result<(), CriticalErrorKind> {
+        let window = samples_of(self.window);
+        let (rolling, channels, rate) = (self.rolling, self.channels, self.rate);
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        // Stdin is read apart, not to miss live input while recognizing
+        let reader = tokio::task::spawn_blocking(move || {
+            let source = PcmSource::new(std::io::stdin().lock(), channels, rate)?;
+            #[allow(clippy::cast_possible_truncation)]
+            let mut samples =
+                rodio::source::UniformSourceIterator::<_, i16>::new(source, 1, SAMPLE_RATE as u32);
+            let mut generator = SignatureGenerator::default();
+            let mut offset = 0;
+            let mut buffer = Vec::with_capacity(FEED_LEN);
+            loop {
+                let missing = window - generator.number_samples() as usize;
+                buffer.clear();
+                buffer.extend(samples.by_ref().take(missing.min(FEED_LEN)));
+                generator.feed(&buffer);
+                let samples_len = generator.number_samples() as usize;
+                if buffer.is_empty() || samples_len >= window {
+                    if samples_len > 0 && sender.send((offset, generator.take_signature())).is_err()
+                    {
+                        break;
+                    }
+                    offset += samples_len;
+                    if buffer.is_empty() || !rolling {
+                        break;
+                    }
+                }
+            }
+            Ok::<_, CriticalErrorKind>(())
+        });
+
+        let recognizer = self.recognizer_options.recognizer()?;
+        let mut last_song = None;
+        while let Some((offset, signature)) = receiver.recv().await {
+            let song = match recognizer.recognize(&signature).await {
+                Ok(response) => parse_song(STDIN.to_string(), &response),
+                Err(e) => Err(e),
+            };
+            if !rolling {
+                self.print_song(&song?)?;
+                continue;
+            }
+            match song {
+                Ok(song) => {
+                    let current = Some((song.artist_name.clone(), song.song_name.clone()));
+                    if current != last_song {
+                        match self.output {
+                            ReportOutput::Table => println!(
+                                "{} - {} - {}",
+                                timestamp(duration_of(offset)),
+                                song.artist_name,
+                                song.song_name
+                            ),
+                            // One song per line, for consumers reading as it goes
+                            ReportOutput::Json => println!(
+                                "{}",
+                                serde_json::json!({
+                                    "offset": duration_of(offset).as_secs_f64(),
+                                    "song": song,
+                                })
+                            ),
+                        }
+                        last_song = current;
+                    }
+                }
+                Err(CriticalErrorKind::NoMatch { .. }) => {}
+                Err(e) => eprintln!("{} : {e}", timestamp(duration_of(offset))),
+            }
+        }
+        reader.await?
+    }
+}
+
+/// Match of the signature in the Shazam catalog
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct ShazamMatch {
+    #[serde(default)]
+    pub id: String,
+    /// Position of the signature in the matched song, in seconds
+    #[serde(default)]
+    pub offset: f64,
+    #[serde(default, rename(deserialize = "timeskew"))]
+    pub time_skew: f64,
+    #[serde(default, rename(deserialize = "frequencyskew"))]
+    pub frequency_skew: f64,
+}
+
+#[derive(Deserialize)]
+struct ShazamResponse {
+    #[serde(default)]
+    matches: Vec<ShazamMatch>,
+    track: ShazamTrack,
+}
+
+#[derive(Deserialize)]
+struct ShazamTrack {
+    key: Option<String>,
+    title: String,
+    subtitle: String,
+    isrc: Option<String>,
+    #[serde(default)]
+    genres: ShazamGenres,
+    #[serde(default)]
+    images: ShazamImages,
+    #[serde(default)]
+    sections: Vec<ShazamSection>,
+}
+
+#[derive(Deserialize, Default)]
+struct ShazamGenres {
+    primary: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct ShazamImages {
+    coverart: Option<String>,
+    coverarthq: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ShazamSection {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    metadata: Vec<ShazamMetadatum>,
 }
 
+#[derive(Deserialize)]
+struct ShazamMetadatum {
+    title: String,
+    text: String,
+}
+
+#[derive(Serialize, Default, Debug)]
 pub struct SongRecognizedMessage {
     pub path: String,
     pub artist_name: String,
     pub album_name: Option<String>,
     pub song_name: String,
+    pub key: Option<String>,
+    pub isrc: Option<String>,
+    pub genre: Option<String>,
+    pub release_year: Option<String>,
+    pub label: Option<String>,
+    pub cover_art: Option<String>,
+    pub cover_art_hq: Option<String>,
+    /// Best match, the other ones being alternates
+    pub matched: Option<ShazamMatch>,
+    pub alternate_matches: Vec<ShazamMatch>,
 }
 
-pub async fn try_recognize_song(
-    path: String,
-    signature: &DecodedSignature,
-) -> Result<SongRecognizedMessage, CriticalErrorKind> {
-    let json_object = recognize_song_from_signature(signature).await?;
-    let mut album_name: Option<String> = None;
-    if let Value::Array(sections) = &json_object["track"]["sections"] {
-        for section in sections {
-            if let Value::String(string) = &section["type"] {
-                if string == "SONG" {
-                    if let Value::Array(metadata) = &section["metadata"] {
-                        for metadatum in metadata {
-                            if let Value::String(title) = &metadatum["title"] {
-                                if title == "Album" {
-                                    if let Value::String(text) = &metadatum["text"] {
-                                        album_name = Some(text.to_string());
-                                    }
-                                }
-                            }
-                        }
-                        break;
-                    }
-                }
+impl std::fmt::Display for SongRecognizedMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Artist : {}", self.artist_name)?;
+        writeln!(
+            f,
+            "Album : {}",
+            self.album_name.as_deref().unwrap_or("Unknown")
+        )?;
+        writeln!(f, "Song : {}", self.song_name)?;
+        for (name, value) in [
+            ("Genre", &self.genre),
+            ("Released", &self.release_year),
+            ("Label", &self.label),
+            ("ISRC", &self.isrc),
+            ("Shazam key", &self.key),
+            ("Cover art", &self.cover_art_hq),
+        ] {
+            if let Some(value) = value {
+                writeln!(f, "{name} : {value}")?;
             }
         }
+        if let Some(matched) = &self.matched {
+            writeln!(
+                f,
+                "Match : {:.2}s in song, time skew {:.5}",
+                matched.offset, matched.time_skew
+            )?;
+        }
+        if !self.alternate_matches.is_empty() {
+            writeln!(f, "Alternate matches : {}", self.alternate_matches.len())?;
+        }
+        write!(f, "Path : {}", self.path)
     }
+}
+
+/// Song of a Shazam response, a missing track being no match and a malformed one an error
+pub fn parse_song(
+    path: String,
+    json_object: &Value,
+) -> Result<SongRecognizedMessage, CriticalErrorKind> {
+    if json_object.get("track").is_none_or(Value::is_null) {
+        return Err(CriticalErrorKind::NoMatch { path });
+    }
+    let response = ShazamResponse::deserialize(json_object).map_err(|error| {
+        CriticalErrorKind::ShazamResponseError {
+            path: path.clone(),
+            error,
+        }
+    })?;
+
+    let track = response.track;
+    let metadatum = |title: &str| {
+        track
+            .sections
+            .iter()
+            .find(|section| section.kind == "SONG")
+            .and_then(|section| section.metadata.iter().find(|m| m.title == title))
+            .map(|m| m.text.clone())
+    };
+    let mut matches = response.matches.into_iter();
     Ok(SongRecognizedMessage {
-        path: path.clone(),
-        artist_name: match &json_object["track"]["subtitle"] {
-            Value::String(string) => string.to_string(),
-            _ => {
-                return Err(CriticalErrorKind::NoMatch { path });
-            }
-        },
-        album_name,
-        song_name: match &json_object["track"]["title"] {
-            Value::String(string) => string.to_string(),
-            _ => {
-                return Err(CriticalErrorKind::NoMatch { path });
-            }
-        },
+        album_name: metadatum("Album"),
+        label: metadatum("Label"),
+        release_year: metadatum("Released"),
+        path,
+        artist_name: track.subtitle,
+        song_name: track.title,
+        key: track.key,
+        isrc: track.isrc,
+        genre: track.genres.primary,
+        cover_art: track.images.coverart,
+        cover_art_hq: track.images.coverarthq,
+        matched: matches.next(),
+        alternate_matches: matches.collect(),
     })
 }
+
+#[test]
+fn shazam_response_tests() {
+    let response = serde_json::json!({
+        "matches": [
+            {"id": "1", "offset": 95.5, "timeskew": 0.0001, "frequencyskew": 0.0},
+            {"id": "2", "offset": 12.0, "timeskew": -0.0002, "frequencyskew": 0.0}
+        ],
+        "track": {
+            "key": "20066955",
+            "title": "Thunderstruck",
+            "subtitle": "AC/DC",
+            "isrc": "AUAP09000014",
+            "genres": {"primary": "Hard Rock"},
+            "images": {"coverart": "https://is1.example/400x400.jpg", "coverarthq": "https://is1.example/800x800.jpg"},
+            "sections": [
+                {"type": "SONG", "metadata": [
+                    {"title": "Album", "text": "The Razors Edge"},
+                    {"title": "Label", "text": "Columbia"},
+                    {"title": "Released", "text": "1990"}
+                ]},
+                {"type": "LYRICS"}
+            ]
+        }
+    });
+    let song = parse_song("a.flac".to_string(), &response).unwrap();
+    assert_eq!(song.artist_name, "AC/DC");
+    assert_eq!(song.song_name, "Thunderstruck");
+    assert_eq!(song.album_name.as_deref(), Some("The Razors Edge"));
+    assert_eq!(song.key.as_deref(), Some("20066955"));
+    assert_eq!(song.isrc.as_deref(), Some("AUAP09000014"));
+    assert_eq!(song.genre.as_deref(), Some("Hard Rock"));
+    assert_eq!(song.release_year.as_deref(), Some("1990"));
+    assert_eq!(song.label.as_deref(), Some("Columbia"));
+    assert_eq!(
+        song.cover_art_hq.as_deref(),
+        Some("https://is1.example/800x800.jpg")
+    );
+    assert!((song.matched.unwrap().offset - 95.5).abs() < f64::EPSILON);
+    assert_eq!(song.alternate_matches.len(), 1);
+    assert_eq!(song.alternate_matches[0].id, "2");
+
+    assert!(matches!(
+        parse_song(
+            "a.flac".to_string(),
+            &serde_json::json!({"matches": [], "tagid": "x"})
+        ),
+        Err(CriticalErrorKind::NoMatch { .. })
+    ));
+    assert!(matches!(
+        parse_song(
+            "a.flac".to_string(),
+            &serde_json::json!({"track": {"title": 1}})
+        ),
+        Err(CriticalErrorKind::ShazamResponseError { .. })
+    ));
+}
@@ -1,11 +1,12 @@
 use super::{config::Config, errors::CriticalErrorKind};
 use gel_derive::Queryable;
+use serde::Serialize;
 
 #[derive(clap::Parser)]
 #[clap(about = "Get statistics")]
 pub struct Stats {}
 
-#[derive(Queryable)]
+#[derive(Queryable, Serialize)]
 pub struct FolderOutput {
     pub name: String,
     pub username: String,
@@ -0,0 +1,177 @@
+use serde::Serialize;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use super::errors::CriticalErrorKind;
+use super::shazam::SongRecognizedMessage;
+
+/// CUE sheets count 75 frames per second
+const CUE_FRAMES_PER_SECOND: u128 = 75;
+
+#[derive(clap::ValueEnum, Clone, Default)]
+pub enum TracklistFormat {
+    #[default]
+    Text,
+    Json,
+    Cue,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TracklistEntry {
+    pub start: f64,
+    pub end: f64,
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+}
+
+/// Timestamped songs recognized over a recording
+#[derive(Serialize, Default)]
+pub struct Tracklist {
+    entries: Vec<TracklistEntry>,
+}
+
+impl Tracklist {
+    #[must_use]
+    pub fn entries(&self) -> &[TracklistEntry] {
+        &self.entries
+    }
+
+    /// Add a window match, extending the last entry when it is the same song
+    pub fn push(&mut self, start: Duration, end: Duration, song: SongRecognizedMessage) {
+        if let Some(last) = self.entries.last_mut()
+            && last.artist == song.artist_name
+            && last.title == song.song_name
+        {
+            last.end = end.as_secs_f64();
+            if last.album.is_none() {
+                last.album = song.album_name;
+            }
+            return;
+        }
+        self.entries.push(TracklistEntry {
+            start: start.as_secs_f64(),
+            end: end.as_secs_f64(),
+            artist: song.artist_name,
+            title: song.song_name,
+            album: song.album_name,
+        });
+    }
+
+    pub fn render(
+        &self,
+        format: &TracklistFormat,
+        path: &str,
+    ) -> Result<String, CriticalErrorKind> {
+        Ok(match format {
+            TracklistFormat::Text => self.to_text(),
+            TracklistFormat::Json => serde_json::to_string_pretty(self)?,
+            TracklistFormat::Cue => self.to_cue(path),
+        })
+    }
+
+    #[must_use]
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for entry in &self.entries {
+            let _ = writeln!(
+                text,
+                "{} - {} - {}",
+                timestamp(Duration::from_secs_f64(entry.start)),
+                entry.artist,
+                entry.title
+            );
+        }
+        text
+    }
+
+    #[must_use]
+    pub fn to_cue(&self, path: &str) -> String {
+        let path = Path::new(path);
+        let file_type = match path.extension().and_then(|e| e.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("mp3") => "MP3",
+            Some(extension) if extension.eq_ignore_ascii_case("aiff") => "AIFF",
+            _ => "WAVE",
+        };
+        let file_name = path
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().to_string());
+        let mut cue = format!("FILE \"{}\" {file_type}\n", cue_escape(&file_name));
+        for (index, entry) in self.entries.iter().enumerate() {
+            let _ = writeln!(cue, "  TRACK {:02} AUDIO", index + 1);
+            let _ = writeln!(cue, "    TITLE \"{}\"", cue_escape(&entry.title));
+            let _ = writeln!(cue, "    PERFORMER \"{}\"", cue_escape(&entry.artist));
+            let _ = writeln!(
+                cue,
+                "    INDEX 01 {}",
+                cue_index(Duration::from_secs_f64(entry.start))
+            );
+        }
+        cue
+    }
+}
+
+fn cue_escape(value: &str) -> String {
+    value.replace('"', "'")
+}
+
+/// Duration as hh:mm:ss
+#[must_use]
+pub fn timestamp(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// CUE index as mm:ss:ff, minutes going over 59
+fn cue_index(duration: Duration) -> String {
+    let frames = duration.as_millis() * CUE_FRAMES_PER_SECOND / 1000;
+    let seconds = frames / CUE_FRAMES_PER_SECOND;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 60,
+        seconds % 60,
+        frames % CUE_FRAMES_PER_SECOND
+    )
+}
+
+#[test]
+fn tracklist_tests() {
+    let song = |artist: &str, title: &str| SongRecognizedMessage {
+        path: "mix.mp3".to_string(),
+        artist_name: artist.to_string(),
+        album_name: None,
+        song_name: title.to_string(),
+        ..SongRecognizedMessage::default()
+    };
+    let mut tracklist = Tracklist::default();
+    tracklist.push(
+        Duration::ZERO,
+        Duration::from_secs(12),
+        song("AC/DC", "Thunderstruck"),
+    );
+    tracklist.push(
+        Duration::from_secs(30),
+        Duration::from_secs(42),
+        song("AC/DC", "Thunderstruck"),
+    );
+    tracklist.push(
+        Duration::from_millis(3_690_500),
+        Duration::from_secs(3702),
+        song("Daft \"Punk\"", "One More Time"),
+    );
+    assert_eq!(tracklist.entries().len(), 2);
+    assert!((tracklist.entries()[0].end - 42.0).abs() < f64::EPSILON);
+    assert_eq!(
+        tracklist.to_text(),
+        "00:00:00 - AC/DC - Thunderstruck\n01:01:30 - Daft \"Punk\" - One More Time\n"
+    );
+    let cue = tracklist.to_cue("/mixes/mix.mp3");
+    assert!(cue.starts_with("FILE \"mix.mp3\" MP3\n  TRACK 01 AUDIO\n"));
+    assert!(cue.contains("    PERFORMER \"Daft 'Punk'\"\n    INDEX 01 61:30:37\n"));
+}
@@ -0,0 +1,230 @@
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::errors::CriticalErrorKind;
+use super::flac_file::FlacFile;
+use super::mp3_file::Mp3File;
+use super::music_file::{MusicFile, MusicFileMut};
+use crate::fingerprinting::ffmpeg_wrapper::find_ffmpeg;
+
+const TRANSCODE_CACHE_DIR: &str = ".cache/critical/transcode";
+/// Content hashes of sources, by path, size and modification time
+const SOURCES_INDEX_DIR: &str = "sources";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Codec {
+    Mp3,
+    Opus,
+}
+
+impl Codec {
+    #[must_use]
+    pub fn extension(&self) -> &str {
+        match self {
+            Codec::Mp3 => "mp3",
+            Codec::Opus => "opus",
+        }
+    }
+
+    fn encoder(self) -> &'static str {
+        match self {
+            Codec::Mp3 => "libmp3lame",
+            Codec::Opus => "libopus",
+        }
+    }
+}
+
+/// Target codec and bitrate of lossless sources, like opus:128k
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transcode {
+    codec: Codec,
+    bitrate: String,
+}
+
+impl Transcode {
+    #[must_use]
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// Only lossless sources are transcoded
+    #[must_use]
+    pub fn applies(&self, source: &Path) -> bool {
+        source
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("flac"))
+    }
+
+    /// Path of the transcoded file in the cache, keyed by source content and settings
+    pub fn cached_path(
+        &self,
+        cache_dir: &Path,
+        source: &Path,
+    ) -> Result<PathBuf, CriticalErrorKind> {
+        let mut hasher = Sha256::new();
+        hasher.update(content_key(cache_dir, source)?.as_bytes());
+        hasher.update(self.codec.extension().as_bytes());
+        hasher.update(self.bitrate.as_bytes());
+        let key = hex(hasher)?;
+        Ok(cache_dir
+            .join(&key[..2])
+            .join(format!("{key}.{}", self.codec.extension())))
+    }
+
+    /// Transcode a source unless it is already in the cache
+    pub fn transcode(
+        &self,
+        ffmpeg: Option<&str>,
+        cache_dir: &Path,
+        source: &Path,
+    ) -> Result<PathBuf, CriticalErrorKind> {
+        let cached_path = self.cached_path(cache_dir, source)?;
+        if cached_path.is_file() {
+            return Ok(cached_path);
+        }
+        let Some(ffmpeg_path) = find_ffmpeg(ffmpeg) else {
+            return Err(CriticalErrorKind::FfmpegNotFound);
+        };
+        if let Some(parent) = cached_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Write beside the final path, so an interrupted transcode never lands in the cache
+        let partial_path = cached_path.with_extension(format!("part.{}", self.codec.extension()));
+        let output = Command::new(ffmpeg_path)
+            .args(["-y", "-v", "error", "-i"])
+            .arg(source)
+            .args(["-map", "0:a", "-map_metadata", "0", "-c:a"])
+            .arg(self.codec.encoder())
+            .args(["-b:a", &self.bitrate])
+            .arg(&partial_path)
+            .output()?;
+        if !output.status.success() {
+            let _ = std::fs::remove_file(&partial_path);
+            return Err(CriticalErrorKind::TranscodeError {
+                path: source.display().to_string(),
+                error: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+
+        // FFMpeg maps Vorbis comments to ID3 frames it does not know, FMPS rating and keywords are written the way they are read
+        if self.codec == Codec::Mp3
+            && let Ok(flac_file) = FlacFile::from_path("", &source.to_string_lossy())
+        {
+            let mut mp3_file = Mp3File::from_path("", &partial_path.to_string_lossy())?;
+            mp3_file.set_rating(flac_file.rating()?);
+            mp3_file.set_keywords(&flac_file.keywords());
+            mp3_file.save()?;
+        }
+        std::fs::rename(partial_path, &cached_path)?;
+        Ok(cached_path)
+    }
+}
+
+fn hex(hasher: Sha256) -> Result<String, CriticalErrorKind> {
+    let mut key = String::new();
+    for byte in hasher.finalize() {
+        write!(key, "{byte:02x}")?;
+    }
+    Ok(key)
+}
+
+/// Hash of a source content, only read again when its path, size or modification time changed
+fn content_key(cache_dir: &Path, source: &Path) -> Result<String, CriticalErrorKind> {
+    let metadata = std::fs::metadata(source)?;
+    let modified = metadata.modified()?.duration_since(std::time::UNIX_EPOCH)?;
+    let mut hasher = Sha256::new();
+    hasher.update(std::path::absolute(source)?.as_os_str().as_encoded_bytes());
+    hasher.update(metadata.len().to_le_bytes());
+    hasher.update(modified.as_nanos().to_le_bytes());
+    let stat_key = hex(hasher)?;
+    let index_path = cache_dir
+        .join(SOURCES_INDEX_DIR)
+        .join(&stat_key[..2])
+        .join(&stat_key);
+    if let Ok(key) = std::fs::read_to_string(&index_path)
+        && key.len() == 64
+    {
+        return Ok(key);
+    }
+
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(source)?, &mut hasher)?;
+    let key = hex(hasher)?;
+    if let Some(parent) = index_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(index_path, &key)?;
+    Ok(key)
+}
+
+pub fn validate_transcode(transcode: &str) -> Result<Transcode, String> {
+    let (codec, bitrate) = transcode.split_once(':').unwrap_or((transcode, "192k"));
+    let codec = match codec {
+        "mp3" => Codec::Mp3,
+        "opus" => Codec::Opus,
+        _ => {
+            return Err(format!(
+                "{codec} is an invalid codec, valid values: mp3, opus"
+            ));
+        }
+    };
+    let digits = bitrate.trim_end_matches(['k', 'K']);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("{bitrate} is an invalid bitrate, like 128k"));
+    }
+    Ok(Transcode {
+        codec,
+        bitrate: bitrate.to_lowercase(),
+    })
+}
+
+#[must_use]
+pub fn default_transcode_cache() -> String {
+    let mut cache_dir = homedir::my_home()
+        .ok()
+        .flatten()
+        .unwrap_or_else(std::env::temp_dir);
+    cache_dir.push(TRANSCODE_CACHE_DIR);
+    cache_dir.to_string_lossy().to_string()
+}
+
+#[test]
+fn transcode_tests() {
+    let transcode = validate_transcode("opus:128K").unwrap();
+    assert_eq!(transcode.codec(), Codec::Opus);
+    assert_eq!(transcode.bitrate, "128k");
+    assert!(validate_transcode("aac:128k").is_err());
+    assert!(validate_transcode("mp3:loud").is_err());
+
+    let source_dir = tempfile::tempdir().unwrap();
+    let source = source_dir.path().join("a.flac");
+    std::fs::write(&source, b"flac").unwrap();
+    assert!(transcode.applies(&source));
+    assert!(!transcode.applies(Path::new("a.mp3")));
+
+    let cached_path = transcode.cached_path(source_dir.path(), &source).unwrap();
+    assert_eq!(cached_path.extension().unwrap(), "opus");
+    let mp3_cached_path = validate_transcode("mp3:128k")
+        .unwrap()
+        .cached_path(source_dir.path(), &source)
+        .unwrap();
+    assert_ne!(cached_path.file_stem(), mp3_cached_path.file_stem());
+
+    // Same size and modification time, the source is not read again
+    let modified = std::fs::metadata(&source).unwrap().modified().unwrap();
+    std::fs::write(&source, b"FLAC").unwrap();
+    let file = std::fs::File::options().write(true).open(&source).unwrap();
+    file.set_modified(modified).unwrap();
+    assert_eq!(
+        transcode.cached_path(source_dir.path(), &source).unwrap(),
+        cached_path
+    );
+    std::fs::write(&source, b"flac, changed").unwrap();
+    assert_ne!(
+        transcode.cached_path(source_dir.path(), &source).unwrap(),
+        cached_path
+    );
+}
@@ -0,0 +1,408 @@
+use md5::{Digest, Md5};
+use rodio::Source;
+use serde::Serialize;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+use tabled::{Table, Tabled};
+
+use super::config::Config;
+use super::errors::CriticalErrorKind;
+use super::filter::Filters;
+use super::folders::local_files;
+use super::helpers::ReportOutput;
+#[cfg(feature = "ffmpeg")]
+use crate::fingerprinting::ffmpeg_wrapper::{decoding_errors, find_ffmpeg};
+
+const OK: &str = "ok";
+const BROKEN: &str = "broken";
+/// Decoded audio shorter than announced by the container by more than this is truncated
+const TRUNCATION_TOLERANCE: Duration = Duration::from_millis(500);
+const ID3V1_LEN: usize = 128;
+const APE_FOOTER_LEN: usize = 32;
+
+#[derive(clap::Parser)]
+#[clap(about = "Verify audio integrity, decoding musics fully")]
+pub struct Verify {
+    /// Only report broken files
+    #[clap(long)]
+    broken: bool,
+
+    /// Store results in musics, for the integrity filter to select broken ones
+    #[clap(long)]
+    store: bool,
+
+    /// Report format
+    #[clap(long, value_enum, default_value_t)]
+    output: ReportOutput,
+
+    /// Filters selecting musics, when no folder is given
+    #[clap(flatten)]
+    filters: Filters,
+
+    /// Folders to verify instead of filtered musics
+    folders: Vec<String>,
+}
+
+#[derive(Serialize, Tabled, Debug)]
+pub struct IntegrityReport {
+    pub path: String,
+    pub status: &'static str,
+    /// Audio MD5 compared with the FLAC STREAMINFO one
+    #[tabled(display("display_option"))]
+    pub md5: Option<&'static str>,
+    /// MPEG frames, for MP3 files
+    #[tabled(display("display_option"))]
+    pub frames: Option<usize>,
+    #[tabled(display("display_option"))]
+    pub sync_errors: Option<usize>,
+    #[tabled(display("display_errors"))]
+    pub errors: Vec<String>,
+}
+
+#[allow(clippy::ref_option)]
+fn display_option<T: std::fmt::Display>(value: &Option<T>) -> String {
+    value.as_ref().map(ToString::to_string).unwrap_or_default()
+}
+
+fn display_errors(errors: &[String]) -> String {
+    errors.join("\n")
+}
+
+impl IntegrityReport {
+    /// Verify a file, decoding FLAC with its MD5 and scanning MP3 frames before a full decode
+    #[must_use]
+    pub fn new(path: &str, ffmpeg_path: Option<&str>) -> Self {
+        let mut report = Self {
+            path: path.to_string(),
+            status: OK,
+            md5: None,
+            frames: None,
+            sync_errors: None,
+            errors: Vec::new(),
+        };
+        let extension = Path::new(path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        let checked = match extension.as_deref() {
+            Some("flac") => report.verify_flac(),
+            Some("mp3") => report
+                .verify_mp3()
+                .and_then(|()| report.verify_decoding(ffmpeg_path)),
+            _ => report.verify_decoding(ffmpeg_path),
+        };
+        if let Err(e) = checked {
+            report.errors.push(e.to_string());
+        }
+        if !report.errors.is_empty() {
+            report.status = BROKEN;
+        }
+        report
+    }
+
+    /// Decode all FLAC frames, comparing the MD5 of their samples with the STREAMINFO one
+    fn verify_flac(&mut self) -> Result<(), CriticalErrorKind> {
+        let tag = metaflac::Tag::read_from_path(&self.path)?;
+        let Some(streaminfo) = tag.get_streaminfo() else {
+            self.errors.push("missing STREAMINFO".to_string());
+            return Ok(());
+        };
+
+        // Samples are hashed interleaved, little endian, on the bytes their bit depth needs
+        let sample_bytes = usize::from(streaminfo.bits_per_sample.div_ceil(8));
+        let mut reader = claxon::FlacReader::open(&self.path)?;
+        let mut blocks = reader.blocks();
+        let mut md5 = Md5::new();
+        let mut samples = 0u64;
+        let mut buffer = Vec::new();
+        loop {
+            match blocks.read_next_or_eof(buffer) {
+                Ok(Some(block)) => {
+                    for sample in 0..block.duration() {
+                        for channel in 0..block.channels() {
+                            md5.update(
+                                &block.sample(channel, sample).to_le_bytes()[..sample_bytes],
+                            );
+                        }
+                    }
+                    samples += u64::from(block.duration());
+                    buffer = block.into_buffer();
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    self.errors.push(format!("decoding failed: {e}"));
+                    break;
+                }
+            }
+        }
+
+        if streaminfo.total_samples != 0 && samples != streaminfo.total_samples {
+            self.errors.push(format!(
+                "truncated, decoded {samples} of {} samples",
+                streaminfo.total_samples
+            ));
+        }
+        if streaminfo.md5.iter().all(|byte| *byte == 0) {
+            self.md5 = Some("unset");
+        } else if md5.finalize().as_slice() == streaminfo.md5.as_slice() {
+            self.md5 = Some("match");
+        } else {
+            self.md5 = Some("mismatch");
+            self.errors
+                .push("audio MD5 differs from STREAMINFO".to_string());
+        }
+        Ok(())
+    }
+
+    fn verify_mp3(&mut self) -> Result<(), CriticalErrorKind> {
+        let (frames, sync_errors) = mpeg_frames(&std::fs::read(&self.path)?);
+        self.frames = Some(frames);
+        self.sync_errors = Some(sync_errors);
+        if frames == 0 {
+            self.errors.push("no MPEG audio frame".to_string());
+        } else if sync_errors > 0 {
+            self.errors.push(format!("{sync_errors} frame sync errors"));
+        }
+        Ok(())
+    }
+
+    /// Decode the whole file with FFMpeg when available, else with Rodio
+    fn verify_decoding(&mut self, ffmpeg_path: Option<&str>) -> Result<(), CriticalErrorKind> {
+        #[cfg(feature = "ffmpeg")]
+        if let Some(ffmpeg_path) = ffmpeg_path {
+            self.errors
+                .extend(decoding_errors(ffmpeg_path, &self.path)?);
+            return Ok(());
+        }
+        #[cfg(not(feature = "ffmpeg"))]
+        let _ = ffmpeg_path;
+
+        // Rodio stops at the first error, which shows as a shorter duration
+        let decoder = rodio::Decoder::new(BufReader::new(std::fs::File::open(&self.path)?))?;
+        let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+        let expected = decoder.total_duration();
+        #[allow(clippy::cast_precision_loss)]
+        let decoded = Duration::from_secs_f64(
+            decoder.count() as f64 / f64::from(channels) / f64::from(sample_rate),
+        );
+        if let Some(expected) = expected
+            && decoded + TRUNCATION_TOLERANCE < expected
+        {
+            self.errors.push(format!(
+                "truncated, decoded {:.1}s of {:.1}s",
+                decoded.as_secs_f64(),
+                expected.as_secs_f64()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Length of the MPEG audio frame starting with a header, if valid
+fn mpeg_frame_len(header: &[u8]) -> Option<usize> {
+    const BITRATES_V1: [[u32; 15]; 3] = [
+        [
+            0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+        ],
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+        ],
+        [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ],
+    ];
+    const BITRATES_V2: [[u32; 15]; 2] = [
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+        ],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    ];
+    const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+    let [first, second, third, ..] = *header else {
+        return None;
+    };
+    if first != 0xff || second & 0xe0 != 0xe0 {
+        return None;
+    }
+    // Version 0 is MPEG 2.5, 1 reserved, 2 MPEG 2 and 3 MPEG 1
+    let version = (second >> 3) & 0b11;
+    // Layer 1 is III, 2 is II and 3 is I, 0 being reserved
+    let layer = (second >> 1) & 0b11;
+    let bitrate_index = usize::from(third >> 4);
+    let sample_rate_index = usize::from((third >> 2) & 0b11);
+    if version == 1
+        || layer == 0
+        || bitrate_index == 0
+        || bitrate_index == 15
+        || sample_rate_index == 3
+    {
+        return None;
+    }
+    let padding = u32::from((third >> 1) & 1);
+    let sample_rate = SAMPLE_RATES[sample_rate_index] >> (3 - version.max(1));
+    let bitrate = 1000
+        * match (version, layer) {
+            (3, _) => BITRATES_V1[usize::from(3 - layer)][bitrate_index],
+            (_, 3) => BITRATES_V2[0][bitrate_index],
+            _ => BITRATES_V2[1][bitrate_index],
+        };
+    let len = match (version, layer) {
+        (_, 3) => (12 * bitrate / sample_rate + padding) * 4,
+        (3, _) | (_, 2) => 144 * bitrate / sample_rate + padding,
+        _ => 72 * bitrate / sample_rate + padding,
+    };
+    usize::try_from(len).ok()
+}
+
+/// MPEG audio frames of an MP3 file, and how many times their sequence breaks
+#[must_use]
+pub fn mpeg_frames(data: &[u8]) -> (usize, usize) {
+    // Skip the ID3v2 tag, its size being stored on 7 bits per byte
+    let mut position = 0;
+    if data.len() >= 10 && data.starts_with(b"ID3") {
+        let size = data[6..10]
+            .iter()
+            .fold(0usize, |size, byte| (size << 7) | usize::from(byte & 0x7f));
+        let footer = if data[5] & 0x10 == 0 { 0 } else { 10 };
+        position = 10 + size + footer;
+    }
+
+    // Trailing ID3v1 and APEv2 tags are not audio
+    let mut end = data.len();
+    if end >= ID3V1_LEN && data[end - ID3V1_LEN..].starts_with(b"TAG") {
+        end -= ID3V1_LEN;
+    }
+    if end >= APE_FOOTER_LEN && data[end - APE_FOOTER_LEN..end].starts_with(b"APETAGEX") {
+        let footer = &data[end - APE_FOOTER_LEN..end];
+        let size = u32::from_le_bytes([footer[12], footer[13], footer[14], footer[15]]);
+        let has_header = footer[23] & 0x80 != 0;
+        let size = usize::try_from(size).unwrap_or(usize::MAX)
+            + if has_header { APE_FOOTER_LEN } else { 0 };
+        end = end.saturating_sub(size);
+    }
+
+    let (mut frames, mut sync_errors) = (0, 0);
+    let mut in_sync = true;
+    while position < end {
+        match mpeg_frame_len(&data[position..end]) {
+            Some(len) if position + len <= end => {
+                frames += 1;
+                position += len;
+                in_sync = true;
+            }
+            // Truncated last frame
+            Some(_) => {
+                sync_errors += 1;
+                break;
+            }
+            None => {
+                if in_sync {
+                    sync_errors += 1;
+                    in_sync = false;
+                }
+                position += 1;
+            }
+        }
+    }
+    (frames, sync_errors)
+}
+
+impl Verify {
+    pub async fn verify(&self, config: Config) -> Result<(), CriticalErrorKind> {
+        let files = Box::pin(local_files(&config, &self.folders, &self.filters)).await?;
+
+        #[cfg(feature = "ffmpeg")]
+        let ffmpeg_path = find_ffmpeg(config.ffmpeg.as_deref());
+        #[cfg(not(feature = "ffmpeg"))]
+        let ffmpeg_path: Option<String> = None;
+
+        let verify_bar = indicatif::ProgressBar::new(files.len() as u64);
+        verify_bar.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template(
+                    "[{elapsed_precise}] Verifying files: {bar:50.cyan/blue} {pos:>7}/{len:7} {msg}",
+                )?
+                .progress_chars("##-"),
+        );
+        let mut reports = Vec::new();
+        for (_, path) in files {
+            scopeguard::defer! {verify_bar.inc(1)};
+            let checked_path = path.clone();
+            let checked_ffmpeg_path = ffmpeg_path.clone();
+            let report = tokio::task::spawn_blocking(move || {
+                IntegrityReport::new(&checked_path, checked_ffmpeg_path.as_deref())
+            })
+            .await?;
+            if self.store && !config.dry {
+                Box::pin(store(&config, &report)).await?;
+            }
+            if !self.broken || report.status == BROKEN {
+                reports.push(report);
+            }
+        }
+        verify_bar.finish_and_clear();
+
+        match self.output {
+            ReportOutput::Table => println!("{}", Table::new(&reports)),
+            ReportOutput::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        }
+        Ok(())
+    }
+}
+
+async fn store(config: &Config, report: &IntegrityReport) -> Result<(), CriticalErrorKind> {
+    let _: Vec<uuid::Uuid> = Box::pin(config.gel.query(
+        STORE_INTEGRITY_QUERY,
+        &(&report.path, report.status, report.errors.join("\n")),
+    ))
+    .await?;
+    Ok(())
+}
+
+const STORE_INTEGRITY_QUERY: &str = r"
+select (
+    update Music
+    filter <str>$0 in .folders@path
+    set {
+        integrity := <str>$1,
+        integrity_errors := <str>$2,
+        integrity_checked := datetime_current()
+    }
+).id
+";
+
+#[test]
+fn integrity_tests() {
+    // MPEG 1 layer III, 128 kbps at 44.1 KHz, frames of 417 bytes without padding
+    let header = [0xff, 0xfb, 0x90, 0x00];
+    assert_eq!(mpeg_frame_len(&header), Some(417));
+    // MPEG 2 layer III, 64 kbps at 22.05 KHz
+    assert_eq!(mpeg_frame_len(&[0xff, 0xf3, 0x80, 0x00]), Some(208));
+    assert_eq!(mpeg_frame_len(&[0xff, 0xfb, 0xf0, 0x00]), None);
+
+    let frames = crate::test_helpers::mp3(8, 1);
+    let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x05tags!".to_vec();
+    data.extend_from_slice(&frames[..5 * 417]);
+    data.extend_from_slice(b"garbage");
+    data.extend_from_slice(&frames[5 * 417..]);
+    data.extend_from_slice(b"TAG");
+    data.extend(std::iter::repeat_n(0, 125));
+    assert_eq!(mpeg_frames(&data), (8, 1));
+
+    // Truncated last frame
+    let mut data = frames[..2 * 417].to_vec();
+    data.truncate(data.len() - 100);
+    assert_eq!(mpeg_frames(&data), (1, 1));
+    assert_eq!(mpeg_frames(b"not an mp3"), (0, 1));
+
+    let mut mp3 = tempfile::Builder::new().suffix(".mp3").tempfile().unwrap();
+    std::io::Write::write_all(&mut mp3, &frames).unwrap();
+    let report = IntegrityReport::new(mp3.path().to_str().unwrap(), None);
+    assert_eq!(report.status, OK);
+    assert_eq!(report.frames, Some(8));
+
+    let report = IntegrityReport::new("missing.flac", None);
+    assert_eq!(report.status, BROKEN);
+    assert_eq!(report.errors.len(), 1);
+}
@@ -0,0 +1,258 @@
+use serde::Serialize;
+use tabled::{Table, Tabled};
+
+use super::config::Config;
+use super::errors::CriticalErrorKind;
+use super::filter::Filters;
+use super::folders::local_files;
+use super::helpers::ReportOutput;
+use super::music_file::{MusicFile, open_music_file};
+use super::recognize::RecognizerOptions;
+use super::shazam::{SongRecognizedMessage, parse_song};
+use crate::fingerprinting::algorithm::SignatureGenerator;
+
+const DEFAULT_THRESHOLD: f64 = 0.8;
+const ARTIST: &str = "artist";
+const TITLE: &str = "title";
+const ALBUM: &str = "album";
+const MISSING: &str = "missing";
+const MISMATCH: &str = "mismatch";
+
+#[derive(clap::Parser)]
+#[clap(about = "Compare tags with Shazam recognition")]
+pub struct VerifyTags {
+    /// Recognition options
+    #[clap(flatten)]
+    recognizer_options: RecognizerOptions,
+
+    /// Minimum similarity, between 0 and 1, for a tag to match its recognition
+    #[clap(long, default_value_t = DEFAULT_THRESHOLD, value_parser = validate_threshold)]
+    threshold: f64,
+
+    /// Write recognized values over mismatching and missing tags
+    #[clap(long)]
+    fix: bool,
+
+    /// Report format
+    #[clap(long, value_enum, default_value_t)]
+    output: ReportOutput,
+
+    /// Filters selecting musics, when no folder is given
+    #[clap(flatten)]
+    filters: Filters,
+
+    /// Folders to verify instead of filtered musics
+    folders: Vec<String>,
+}
+
+fn validate_threshold(threshold: &str) -> Result<f64, String> {
+    match threshold.parse::<f64>() {
+        Ok(threshold) if (0.0..=1.0).contains(&threshold) => Ok(threshold),
+        _ => Err(format!("{threshold} is not a number between 0 and 1")),
+    }
+}
+
+#[derive(Serialize, Tabled, Debug, PartialEq)]
+pub struct TagIssue {
+    pub path: String,
+    pub field: &'static str,
+    pub status: &'static str,
+    pub tag: String,
+    pub recognized: String,
+}
+
+/// Lowercase alphanumeric words, ignoring punctuation and a leading article
+#[must_use]
+pub fn normalize(value: &str) -> String {
+    let words = value
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>();
+    let words = words.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        ["the", rest @ ..] if !rest.is_empty() => rest.join(" "),
+        _ => words.join(" "),
+    }
+}
+
+/// Similarity between 0 and 1 of normalized values
+#[must_use]
+pub fn similarity(left: &str, right: &str) -> f64 {
+    strsim::normalized_levenshtein(&normalize(left), &normalize(right))
+}
+
+fn compare(
+    issues: &mut Vec<TagIssue>,
+    path: &str,
+    field: &'static str,
+    tag: &str,
+    recognized: &str,
+    threshold: f64,
+) {
+    let status = if normalize(tag).is_empty() {
+        MISSING
+    } else if similarity(tag, recognized) < threshold {
+        MISMATCH
+    } else {
+        return;
+    };
+    issues.push(TagIssue {
+        path: path.to_string(),
+        field,
+        status,
+        tag: tag.to_string(),
+        recognized: recognized.to_string(),
+    });
+}
+
+/// Missing and mismatching tags of a file, the album being skipped when not recognized
+#[must_use]
+pub fn compare_tags(
+    music_file: &dyn MusicFile,
+    song: &SongRecognizedMessage,
+    threshold: f64,
+) -> Vec<TagIssue> {
+    let path = music_file.path();
+    let mut issues = Vec::new();
+    compare(
+        &mut issues,
+        path,
+        ARTIST,
+        music_file.artist(),
+        &song.artist_name,
+        threshold,
+    );
+    compare(
+        &mut issues,
+        path,
+        TITLE,
+        music_file.title(),
+        &song.song_name,
+        threshold,
+    );
+    if let Some(album_name) = &song.album_name {
+        compare(
+            &mut issues,
+            path,
+            ALBUM,
+            music_file.album(),
+            album_name,
+            threshold,
+        );
+    }
+    issues
+}
+
+impl VerifyTags {
+    pub async fn verify_tags(&self, config: Config) -> Result<(), CriticalErrorKind> {
+        let files = Box::pin(local_files(&config, &self.folders, &self.filters)).await?;
+
+        let verify_bar = indicatif::ProgressBar::new(files.len() as u64);
+        verify_bar.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template(
+                    "[{elapsed_precise}] Verifying files: {bar:50.cyan/blue} {pos:>7}/{len:7} {msg}",
+                )?
+                .progress_chars("##-"),
+        );
+
+        let recognizer = self.recognizer_options.recognizer()?;
+        let mut issues = Vec::new();
+        for (folder, path) in files {
+            scopeguard::defer! {verify_bar.inc(1)};
+            let result = async {
+                let mut music_file = open_music_file(&folder, &path)?;
+                let signature =
+                    SignatureGenerator::make_signature_from_file(&path, config.ffmpeg.as_deref())?;
+                let response = recognizer.recognize(&signature).await?;
+                let song = parse_song(path.clone(), &response)?;
+                let file_issues = compare_tags(music_file.as_ref(), &song, self.threshold);
+                if self.fix && !file_issues.is_empty() {
+                    for issue in &file_issues {
+                        match issue.field {
+                            ARTIST => music_file.set_artist(&issue.recognized),
+                            TITLE => music_file.set_title(&issue.recognized),
+                            _ => music_file.set_album(&issue.recognized),
+                        }
+                    }
+                    if config.dry {
+                        verify_bar
+                            .println(format!("{path} : would fix {} tags", file_issues.len()));
+                    } else {
+                        music_file.save()?;
+                    }
+                }
+                Ok::<_, CriticalErrorKind>(file_issues)
+            };
+            match Box::pin(result).await {
+                Ok(file_issues) => issues.extend(file_issues),
+                Err(e) => verify_bar.println(format!("{path} : {e}")),
+            }
+        }
+        verify_bar.finish_and_clear();
+
+        match self.output {
+            ReportOutput::Table => println!("{}", Table::new(&issues)),
+            ReportOutput::Json => println!("{}", serde_json::to_string_pretty(&issues)?),
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn tag_tests() {
+    assert_eq!(normalize("  AC/DC "), "ac dc");
+    assert_eq!(normalize("The Beatles"), "beatles");
+    assert_eq!(normalize("The"), "the");
+    assert!(similarity("Thunderstruck (Live)", "thunderstruck live") > 0.99);
+    assert!(similarity("Beatles", "The Beatles") > 0.99);
+    assert!(similarity("Back In Black", "Back in Blak") >= DEFAULT_THRESHOLD);
+    assert!(similarity("Track 01", "Thunderstruck") < DEFAULT_THRESHOLD);
+
+    let mut issues = Vec::new();
+    compare(
+        &mut issues,
+        "a.flac",
+        ARTIST,
+        "AC-DC",
+        "AC/DC",
+        DEFAULT_THRESHOLD,
+    );
+    compare(
+        &mut issues,
+        "a.flac",
+        TITLE,
+        "",
+        "Thunderstruck",
+        DEFAULT_THRESHOLD,
+    );
+    compare(
+        &mut issues,
+        "a.flac",
+        ALBUM,
+        "Unknown",
+        "The Razors Edge",
+        DEFAULT_THRESHOLD,
+    );
+    assert_eq!(
+        issues,
+        vec![
+            TagIssue {
+                path: "a.flac".to_string(),
+                field: TITLE,
+                status: MISSING,
+                tag: String::new(),
+                recognized: "Thunderstruck".to_string(),
+            },
+            TagIssue {
+                path: "a.flac".to_string(),
+                field: ALBUM,
+                status: MISMATCH,
+                tag: "Unknown".to_string(),
+                recognized: "The Razors Edge".to_string(),
+            },
+        ]
+    );
+    assert!(validate_threshold("1.5").is_err());
+}
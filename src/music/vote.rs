@@ -0,0 +1,268 @@
+use serde::Serialize;
+use std::time::Duration;
+
+use super::errors::CriticalErrorKind;
+use super::shazam::SongRecognizedMessage;
+use super::tracklist::timestamp;
+use super::verify_tags::normalize;
+use crate::fingerprinting::algorithm::{SignatureGenerator, duration_of, samples_of};
+use crate::fingerprinting::signature_format::DecodedSignature;
+
+/// Start of evenly spaced windows, centered at 25%, 50% and 75% of the duration for 3 of them.
+/// Windows clamped to the same start in short files are only taken once, not to vote twice.
+#[must_use]
+pub fn window_offsets(total: Duration, windows: usize, window: Duration) -> Vec<Duration> {
+    let latest = total.saturating_sub(window);
+    let mut offsets = (1..=windows)
+        .map(|index| {
+            #[allow(clippy::cast_precision_loss)]
+            let center = total.mul_f64(index as f64 / (windows + 1) as f64);
+            center.saturating_sub(window / 2).min(latest)
+        })
+        .collect::<Vec<_>>();
+    offsets.dedup();
+    offsets
+}
+
+/// Signatures of evenly spaced windows of a file, with their offset
+pub fn window_signatures(
+    file_path: &str,
+    ffmpeg: Option<&str>,
+    windows: usize,
+    window: Duration,
+) -> Result<Vec<(Duration, DecodedSignature)>, CriticalErrorKind> {
+    if let Some(total) = SignatureGenerator::file_duration(file_path) {
+        return window_offsets(total, windows, window)
+            .into_iter()
+            .map(|offset| {
+                let signature = SignatureGenerator::make_signature_from_window(
+                    file_path,
+                    ffmpeg,
+                    Some(offset),
+                    window,
+                )?;
+                Ok((offset, signature))
+            })
+            .collect();
+    }
+
+    // Without a container duration, windows are taken from the whole decoded file
+    let samples = SignatureGenerator::decode_file(file_path, ffmpeg)?;
+    Ok(window_offsets(duration_of(samples.len()), windows, window)
+        .into_iter()
+        .map(|offset| {
+            let start = samples_of(offset).min(samples.len());
+            let end = (start + samples_of(window)).min(samples.len());
+            (
+                offset,
+                SignatureGenerator::make_signature_from_buffer(&samples[start..end]),
+            )
+        })
+        .collect())
+}
+
+/// What a window was recognized as
+#[derive(Serialize, Debug)]
+pub struct WindowRecognition {
+    pub offset: f64,
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Song most windows agree on
+#[derive(Serialize, Debug)]
+pub struct Consensus {
+    pub song: SongRecognizedMessage,
+    pub votes: usize,
+    /// Share of the windows voting for the song
+    pub confidence: f64,
+    /// Whether windows recognized different songs
+    pub disagreement: bool,
+    pub windows: Vec<WindowRecognition>,
+}
+
+/// Same song for Shazam, or else same normalized artist and title
+fn song_identity(song: &SongRecognizedMessage) -> String {
+    song.key.clone().unwrap_or_else(|| {
+        format!(
+            "{}\n{}",
+            normalize(&song.artist_name),
+            normalize(&song.song_name)
+        )
+    })
+}
+
+impl Consensus {
+    /// Vote between recognized windows, earlier windows winning ties.
+    /// Without any match, the first error other than no match is returned.
+    pub fn new(
+        path: &str,
+        mut recognitions: Vec<(Duration, Result<SongRecognizedMessage, CriticalErrorKind>)>,
+    ) -> Result<Self, CriticalErrorKind> {
+        recognitions.sort_by_key(|(offset, _)| *offset);
+        let identities = recognitions
+            .iter()
+            .filter_map(|(_, song)| song.as_ref().ok().map(song_identity))
+            .collect::<Vec<_>>();
+        let mut best: Option<(&String, usize)> = None;
+        for identity in &identities {
+            let votes = identities.iter().filter(|other| *other == identity).count();
+            if best.is_none_or(|(_, best_votes)| votes > best_votes) {
+                best = Some((identity, votes));
+            }
+        }
+        let Some((winner, votes)) = best.map(|(identity, votes)| (identity.clone(), votes)) else {
+            return Err(recognitions
+                .into_iter()
+                .find_map(|(_, song)| match song {
+                    Err(CriticalErrorKind::NoMatch { .. }) | Ok(_) => None,
+                    Err(e) => Some(e),
+                })
+                .unwrap_or(CriticalErrorKind::NoMatch {
+                    path: path.to_string(),
+                }));
+        };
+
+        let disagreement = identities.iter().any(|identity| *identity != winner);
+        #[allow(clippy::cast_precision_loss)]
+        let confidence = votes as f64 / recognitions.len() as f64;
+        let mut song = None;
+        let mut windows = Vec::new();
+        for (offset, recognition) in recognitions {
+            let offset = offset.as_secs_f64();
+            windows.push(match recognition {
+                Ok(recognized) => {
+                    let window = WindowRecognition {
+                        offset,
+                        artist: Some(recognized.artist_name.clone()),
+                        title: Some(recognized.song_name.clone()),
+                        error: None,
+                    };
+                    if song.is_none() && song_identity(&recognized) == winner {
+                        song = Some(recognized);
+                    }
+                    window
+                }
+                Err(e) => WindowRecognition {
+                    offset,
+                    artist: None,
+                    title: None,
+                    error: Some(e.to_string()),
+                },
+            });
+        }
+        Ok(Self {
+            song: song.unwrap_or_default(),
+            votes,
+            confidence,
+            disagreement,
+            windows,
+        })
+    }
+}
+
+impl std::fmt::Display for Consensus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{}", self.song)?;
+        write!(
+            f,
+            "Confidence : {:.0}% ({} of {} windows)",
+            self.confidence * 100.0,
+            self.votes,
+            self.windows.len()
+        )?;
+        if self.disagreement {
+            write!(f, "\nWindows disagree :")?;
+            for window in &self.windows {
+                let recognized = match (&window.artist, &window.title, &window.error) {
+                    (Some(artist), Some(title), _) => format!("{artist} - {title}"),
+                    (_, _, Some(error)) => error.clone(),
+                    _ => String::new(),
+                };
+                write!(
+                    f,
+                    "\n{} : {recognized}",
+                    timestamp(Duration::from_secs_f64(window.offset))
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn vote_tests() {
+    let song = |artist: &str, title: &str| SongRecognizedMessage {
+        path: "a.flac".to_string(),
+        artist_name: artist.to_string(),
+        song_name: title.to_string(),
+        ..SongRecognizedMessage::default()
+    };
+    let no_match = || CriticalErrorKind::NoMatch {
+        path: "a.flac".to_string(),
+    };
+
+    assert_eq!(
+        window_offsets(Duration::from_mins(4), 3, Duration::from_secs(12)),
+        vec![
+            Duration::from_secs(54),
+            Duration::from_secs(114),
+            Duration::from_secs(174)
+        ]
+    );
+    assert_eq!(
+        window_offsets(Duration::from_secs(10), 2, Duration::from_secs(12)),
+        vec![Duration::ZERO]
+    );
+    assert_eq!(
+        window_offsets(Duration::from_secs(20), 3, Duration::from_secs(12)),
+        vec![
+            Duration::ZERO,
+            Duration::from_secs(4),
+            Duration::from_secs(8)
+        ]
+    );
+
+    let consensus = Consensus::new(
+        "a.flac",
+        vec![
+            (Duration::from_secs(174), Ok(song("AC/DC", "Thunderstruck"))),
+            (Duration::from_secs(54), Err(no_match())),
+            (Duration::from_secs(114), Ok(song("AC-DC", "thunderstruck"))),
+            (Duration::from_secs(234), Ok(song("Crowd", "Applause"))),
+        ],
+    )
+    .unwrap();
+    assert_eq!(consensus.song.artist_name, "AC-DC");
+    assert_eq!(consensus.votes, 2);
+    assert!((consensus.confidence - 0.5).abs() < f64::EPSILON);
+    assert!(consensus.disagreement);
+    assert_eq!(consensus.windows[0].error, Some(no_match().to_string()));
+
+    let consensus = Consensus::new(
+        "a.flac",
+        vec![(Duration::ZERO, Ok(song("AC/DC", "Thunderstruck")))],
+    )
+    .unwrap();
+    assert!(!consensus.disagreement);
+    assert!((consensus.confidence - 1.0).abs() < f64::EPSILON);
+
+    assert!(matches!(
+        Consensus::new("a.flac", vec![(Duration::ZERO, Err(no_match()))]),
+        Err(CriticalErrorKind::NoMatch { .. })
+    ));
+    assert!(matches!(
+        Consensus::new(
+            "a.flac",
+            vec![
+                (Duration::ZERO, Err(no_match())),
+                (
+                    Duration::from_secs(1),
+                    Err(CriticalErrorKind::FfmpegNotFound)
+                )
+            ]
+        ),
+        Err(CriticalErrorKind::FfmpegNotFound)
+    ));
+}
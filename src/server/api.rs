@@ -0,0 +1,253 @@
+use axum::extract::{Query, State};
+use axum::http::{StatusCode, header};
+use axum::middleware;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::auth::{Credentials, authenticate};
+use super::{resolve_filter, status_code};
+use crate::music::artists::{ARTISTS_QUERY, ArtistOutput};
+use crate::music::config::Config;
+use crate::music::errors::CriticalErrorKind;
+use crate::music::filter::Filter;
+use crate::music::music_result::MusicResult;
+use crate::music::playlist::{PLAYLIST_QUERY, Playlist};
+use crate::music::remove::Remove;
+use crate::music::scan::Scan;
+use crate::music::stats::{FolderOutput, Stats};
+
+const OPENAPI: &str = include_str!("openapi.json");
+const DEFAULT_NAME: &str = "default";
+
+/// Errors serialized as {"error": message}
+pub struct ApiError(CriticalErrorKind);
+
+impl<E: Into<CriticalErrorKind>> From<E> for ApiError {
+    fn from(error: E) -> Self {
+        Self(error.into())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (
+            status_code(&self.0),
+            Json(serde_json::json!({"error": self.0.to_string()})),
+        )
+            .into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct MusicsQuery {
+    preset: Option<String>,
+    filter: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PlaylistRequest {
+    name: Option<String>,
+    preset: Option<String>,
+    /// Filters in key=value form, musics matching any of them are selected
+    #[serde(default)]
+    filters: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ScanRequest {
+    folders: Vec<String>,
+    #[serde(default)]
+    clean: bool,
+}
+
+#[derive(Deserialize)]
+struct RemoveRequest {
+    paths: Vec<String>,
+}
+
+/// JSON API over the library, described by /openapi.json
+///
+/// With credentials, the whole API is protected by them and the routes changing the library are
+/// exposed, without credentials only reading routes are
+pub fn router(config: Config, credentials: Option<Credentials>) -> Router {
+    let router = Router::new()
+        .route("/openapi.json", get(openapi))
+        .route("/musics", get(musics))
+        .route("/artists", get(artists))
+        .route("/stats", get(stats))
+        .route("/playlists", post(playlist));
+    let Some(credentials) = credentials else {
+        return router.with_state(config);
+    };
+    router
+        .route("/scan", post(scan))
+        .route("/paths", delete(remove))
+        .with_state(config)
+        .layer(middleware::from_fn_with_state(
+            Arc::new(credentials),
+            authenticate,
+        ))
+}
+
+async fn openapi() -> Response {
+    ([(header::CONTENT_TYPE, "application/json")], OPENAPI).into_response()
+}
+
+async fn filter_musics(
+    config: &Config,
+    filter: &Filter,
+) -> Result<Vec<MusicResult>, CriticalErrorKind> {
+    let music_filter = serde_json::to_string(filter)?;
+    Ok(Box::pin(config.gel.query(PLAYLIST_QUERY, &(music_filter,))).await?)
+}
+
+async fn musics(
+    State(config): State<Config>,
+    Query(query): Query<MusicsQuery>,
+) -> Result<Json<Vec<MusicResult>>, ApiError> {
+    let filter = resolve_filter(query.preset.as_deref(), query.filter.as_deref())?;
+    Ok(Json(Box::pin(filter_musics(&config, &filter)).await?))
+}
+
+async fn artists(State(config): State<Config>) -> Result<Json<Vec<ArtistOutput>>, ApiError> {
+    Ok(Json(Box::pin(config.gel.query(ARTISTS_QUERY, &())).await?))
+}
+
+async fn stats(State(config): State<Config>) -> Result<Json<Vec<FolderOutput>>, ApiError> {
+    Ok(Json(Box::pin(Stats {}.stats(config)).await?))
+}
+
+/// Generate a playlist from a preset or filters, musics keep the order of the first filter selecting them
+async fn playlist(
+    State(config): State<Config>,
+    Json(request): Json<PlaylistRequest>,
+) -> Result<Json<Playlist>, ApiError> {
+    let mut filters = Vec::new();
+    if request.preset.is_some() || request.filters.is_empty() {
+        filters.push(resolve_filter(request.preset.as_deref(), None)?);
+    }
+    for filter in &request.filters {
+        filters.push(resolve_filter(None, Some(filter))?);
+    }
+    let mut seen = HashSet::new();
+    let mut musics = Vec::new();
+    for filter in &filters {
+        for music in Box::pin(filter_musics(&config, filter)).await? {
+            if seen.insert(music.clone()) {
+                musics.push(music);
+            }
+        }
+    }
+    let name = request
+        .name
+        .or(request.preset)
+        .unwrap_or_else(|| DEFAULT_NAME.to_string());
+    Ok(Json(Playlist::new(&name, &musics)))
+}
+
+async fn scan(
+    State(config): State<Config>,
+    Json(request): Json<ScanRequest>,
+) -> Result<Json<Vec<FolderOutput>>, ApiError> {
+    let scan = Scan::new(&request.folders, request.clean);
+    Box::pin(scan.scan(config.clone())).await?;
+    Ok(Json(Box::pin(Stats {}.stats(config)).await?))
+}
+
+async fn remove(
+    State(config): State<Config>,
+    Json(request): Json<RemoveRequest>,
+) -> Result<StatusCode, ApiError> {
+    let remove = Remove::new(&request.paths);
+    Box::pin(remove.remove(config.gel, config.dry)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[tokio::test]
+async fn api_tests() {
+    use axum::body::Body;
+    use axum::extract::Request;
+    use tower::ServiceExt;
+
+    let config = Config::new("gel://user@localhost:5656/main".to_string(), true, false).unwrap();
+    let router = router(config.clone(), None);
+
+    let response = router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/openapi.json")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), OPENAPI.len())
+        .await
+        .unwrap();
+    let openapi: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    for path in [
+        "/musics",
+        "/artists",
+        "/stats",
+        "/playlists",
+        "/scan",
+        "/paths",
+    ] {
+        assert!(
+            openapi["paths"].get(path).is_some(),
+            "{path} not documented"
+        );
+    }
+
+    let response = router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/musics?filter=min_length%3D10,max_length%3D5")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), 1024)
+        .await
+        .unwrap();
+    let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(error["error"].as_str().unwrap().contains("length"));
+
+    let response = router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/playlists")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"preset": "unknown"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let scan_request = || {
+        Request::builder()
+            .method("POST")
+            .uri("/scan")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"folders": ["/"]}"#))
+            .unwrap()
+    };
+    let response = router.oneshot(scan_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let protected = super::api::router(config, Some(Credentials::new("user", "secret")));
+    let response = protected.oneshot(scan_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
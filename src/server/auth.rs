@@ -0,0 +1,135 @@
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use md5::{Digest, Md5};
+use std::fmt::Write;
+use std::sync::Arc;
+
+const AUTHENTICATE_CHALLENGE: &str = "Basic realm=\"critical\"";
+
+/// User and password the server requires
+#[derive(Clone)]
+pub struct Credentials {
+    user: String,
+    password: String,
+}
+
+impl Credentials {
+    #[must_use]
+    pub fn new(user: &str, password: &str) -> Self {
+        Self {
+            user: user.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    /// Whether a request carries the credentials, with HTTP basic authentication or Subsonic parameters
+    #[must_use]
+    pub fn authorize(&self, headers: &HeaderMap, query: Option<&str>) -> bool {
+        if let Some((user, password)) = basic_credentials(headers) {
+            return self.matches(&user, &password);
+        }
+        let params: Vec<(String, String)> = query
+            .map(|query| {
+                url::form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.check_subsonic(&params) == Ok(true)
+    }
+
+    /// Whether Subsonic parameters carry the credentials: the user u with a password p, plain or
+    /// enc: hex encoded, or a token t made of the md5 of the password and a salt s.
+    /// The error is the name of a missing parameter.
+    pub fn check_subsonic(&self, params: &[(String, String)]) -> Result<bool, &'static str> {
+        let get = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let user = constant_time_eq(get("u").ok_or("u")?.as_bytes(), self.user.as_bytes());
+        let password = match (get("p"), get("t"), get("s")) {
+            (Some(password), _, _) => decode_password(password).is_some_and(|password| {
+                constant_time_eq(password.as_bytes(), self.password.as_bytes())
+            }),
+            (None, Some(token), Some(salt)) => {
+                let mut hasher = Md5::new();
+                hasher.update(self.password.as_bytes());
+                hasher.update(salt.as_bytes());
+                constant_time_eq(
+                    token.to_ascii_lowercase().as_bytes(),
+                    hex(&hasher.finalize()).as_bytes(),
+                )
+            }
+            _ => return Err("p"),
+        };
+        Ok(user & password)
+    }
+
+    #[must_use]
+    pub fn matches(&self, user: &str, password: &str) -> bool {
+        constant_time_eq(user.as_bytes(), self.user.as_bytes())
+            & constant_time_eq(password.as_bytes(), self.password.as_bytes())
+    }
+}
+
+/// Only let requests with the server credentials through
+pub async fn authenticate(
+    State(credentials): State<Arc<Credentials>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if credentials.authorize(request.headers(), request.uri().query()) {
+        return next.run(request).await;
+    }
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, AUTHENTICATE_CHALLENGE)],
+    )
+        .into_response()
+}
+
+/// Compare secrets in a time that does not depend on where they differ
+#[must_use]
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// User and password of an Authorization: Basic header
+fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let encoded = headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let (user, password) = String::from_utf8(decoded)
+        .ok()?
+        .split_once(':')
+        .map(|(user, password)| (user.to_string(), password.to_string()))?;
+    Some((user, password))
+}
+
+fn decode_password(password: &str) -> Option<String> {
+    let Some(encoded) = password.strip_prefix("enc:") else {
+        return Some(password.to_string());
+    };
+    let bytes = (0..encoded.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(encoded.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
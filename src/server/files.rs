@@ -0,0 +1,166 @@
+use axum::Router;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use serde::Deserialize;
+use std::sync::Arc;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+use super::auth::{Credentials, authenticate};
+use super::{ServerState, resolve_filter};
+use crate::music::errors::CriticalErrorKind;
+use crate::music::links::LinkOptions;
+use crate::music::music_result::MusicResult;
+use crate::music::playlist::{PLAYLIST_QUERY, Playlist};
+
+const M3U_MIME_TYPE: &str = "audio/x-mpegurl";
+
+#[derive(Deserialize)]
+struct PlaylistQuery {
+    /// Name of a default filter, like best-4.5
+    preset: Option<String>,
+    /// Filter in key=value form, like min_rating=4,genre=Rock
+    filter: Option<String>,
+    name: Option<String>,
+}
+
+/// Files and playlists, behind the credentials when some are configured
+pub fn router(state: ServerState, credentials: Option<Credentials>) -> Router {
+    let router = Router::new()
+        .route("/playlist", get(playlist))
+        .route("/{*path}", get(file))
+        .with_state(state);
+    match credentials {
+        Some(credentials) => router.layer(middleware::from_fn_with_state(
+            Arc::new(credentials),
+            authenticate,
+        )),
+        None => router,
+    }
+}
+
+async fn file(
+    State(state): State<ServerState>,
+    Path(path): Path<String>,
+    request: Request,
+) -> Response {
+    match state.resolve(&path) {
+        Ok(path) => ServeFile::new(path).oneshot(request).await.into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+async fn playlist(
+    State(state): State<ServerState>,
+    Query(query): Query<PlaylistQuery>,
+    headers: HeaderMap,
+) -> Result<Response, CriticalErrorKind> {
+    let filter = resolve_filter(query.preset.as_deref(), query.filter.as_deref())?;
+    let Some(host) = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+    else {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+
+    let music_filter = serde_json::to_string(&filter)?;
+    let musics: Vec<MusicResult> =
+        Box::pin(state.gel.query(PLAYLIST_QUERY, &(music_filter,))).await?;
+    let name = query
+        .name
+        .or(query.preset)
+        .unwrap_or_else(|| "default".to_string());
+    let playlist = Playlist::new(&name, &musics);
+    let m3u = playlist.m3u(&musics, &LinkOptions::served_by(host), None)?;
+    Ok(([(header::CONTENT_TYPE, M3U_MIME_TYPE)], m3u).into_response())
+}
+
+#[tokio::test]
+async fn file_tests() {
+    use axum::body::Body;
+
+    let folder = tempfile::tempdir().unwrap();
+    let outside = tempfile::tempdir().unwrap();
+    let music_path = folder.path().join("AC DC").join("a.flac");
+    std::fs::create_dir_all(music_path.parent().unwrap()).unwrap();
+    std::fs::write(&music_path, b"fLaC0123").unwrap();
+    std::fs::write(outside.path().join("secret.flac"), b"secret").unwrap();
+
+    let gel_config = gel_tokio::Builder::new()
+        .dsn("gel://user@localhost:5656/main")
+        .build()
+        .unwrap();
+    let state = ServerState::with_folders(
+        gel_tokio::Client::new(&gel_config),
+        vec![folder.path().canonicalize().unwrap()],
+    );
+    let router = router(state.clone(), None);
+    let get = |uri: String| {
+        Request::builder()
+            .uri(uri)
+            .header(header::RANGE, "bytes=4-")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let link =
+        crate::music::links::default_http_link("localhost", &music_path.canonicalize().unwrap())
+            .unwrap();
+    let response = router
+        .clone()
+        .oneshot(get(link.path().to_string()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "audio/flac");
+    let body = axum::body::to_bytes(response.into_body(), 1024)
+        .await
+        .unwrap();
+    assert_eq!(&body[..], b"0123");
+
+    let response = router
+        .clone()
+        .oneshot(get("/AC%20DC/a.flac".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+
+    let secret = outside.path().join("secret.flac").canonicalize().unwrap();
+    let response = router
+        .clone()
+        .oneshot(get(format!("{}", secret.display())))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = router
+        .oneshot(get("/AC%20DC/../../missing.flac".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let router = super::files::router(state, Some(Credentials::new("admin", "sesame")));
+    let response = router
+        .clone()
+        .oneshot(get("/AC%20DC/a.flac".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = router
+        .clone()
+        .oneshot(get("/AC%20DC/a.flac?u=admin&p=sesame".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    // Basic YWRtaW46c2VzYW1l is admin:sesame
+    let mut request = get("/AC%20DC/a.flac".to_string());
+    request.headers_mut().insert(
+        header::AUTHORIZATION,
+        "Basic YWRtaW46c2VzYW1l".parse().unwrap(),
+    );
+    let response = router.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+}
@@ -0,0 +1,114 @@
+pub mod api;
+pub mod auth;
+pub mod files;
+pub mod subsonic;
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::music::errors::CriticalErrorKind;
+use crate::music::filter::{DEFAULT_FILTERS, Filter, validate_filters};
+use crate::music::helpers::public_ip;
+
+#[derive(Clone)]
+pub struct ServerState {
+    pub gel: gel_tokio::Client,
+    folders: Arc<Vec<PathBuf>>,
+}
+
+impl ServerState {
+    /// Load the folders registered for the current host, only their files are served
+    pub async fn new(gel: gel_tokio::Client) -> Result<Self, CriticalErrorKind> {
+        let ipv4 = public_ip().await?;
+        let username = whoami::username();
+        let names: Vec<String> =
+            Box::pin(gel.query(HOST_FOLDERS_QUERY, &(&ipv4, &username))).await?;
+        let mut folders = Vec::new();
+        for name in names {
+            match Path::new(&name).canonicalize() {
+                Ok(folder) => folders.push(folder),
+                Err(e) => eprintln!("{name} : folder not available, {e}"),
+            }
+        }
+        Ok(Self::with_folders(gel, folders))
+    }
+
+    #[must_use]
+    pub fn with_folders(gel: gel_tokio::Client, folders: Vec<PathBuf>) -> Self {
+        Self {
+            gel,
+            folders: Arc::new(folders),
+        }
+    }
+
+    #[must_use]
+    pub fn folders(&self) -> &[PathBuf] {
+        &self.folders
+    }
+
+    /// Resolve a requested path, absolute or relative to a folder, to a file inside known folders.
+    /// Anything else is not found, whether it exists or not, so files outside folders cannot be probed.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, StatusCode> {
+        let relative = path.trim_start_matches('/');
+        let absolute = Path::new("/").join(relative);
+        let candidates = self
+            .folders
+            .iter()
+            .filter(|folder| absolute.starts_with(folder))
+            .map(|_| absolute.clone())
+            .chain(self.folders.iter().map(|folder| folder.join(relative)));
+        for candidate in candidates {
+            if let Ok(candidate) = candidate.canonicalize()
+                && self
+                    .folders
+                    .iter()
+                    .any(|folder| candidate.starts_with(folder))
+                && candidate.is_file()
+            {
+                return Ok(candidate);
+            }
+        }
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+/// Filter of a request, a default filter name or key=value pairs like min_rating=4,genre=Rock
+pub fn resolve_filter(
+    preset: Option<&str>,
+    filter: Option<&str>,
+) -> Result<Filter, CriticalErrorKind> {
+    match (preset, filter) {
+        (Some(preset), None) => match DEFAULT_FILTERS.get(preset) {
+            Some(filter) => Ok(filter.clone()),
+            None => Err(CriticalErrorKind::InvalidFilter(format!(
+                "{preset} is an unknown preset"
+            ))),
+        },
+        (None, Some(filter)) => validate_filters(filter).map_err(CriticalErrorKind::InvalidFilter),
+        (None, None) => Ok(Filter::default()),
+        (Some(_), Some(_)) => Err(CriticalErrorKind::InvalidFilter(
+            "preset and filter are mutually exclusive".to_string(),
+        )),
+    }
+}
+
+fn status_code(error: &CriticalErrorKind) -> StatusCode {
+    match error {
+        CriticalErrorKind::InvalidFilter(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl IntoResponse for CriticalErrorKind {
+    fn into_response(self) -> Response {
+        (status_code(&self), self.to_string()).into_response()
+    }
+}
+
+const HOST_FOLDERS_QUERY: &str = r"
+select Folder.name
+filter Folder.ipv4 = <str>$0 and Folder.username = <str>$1
+order by Folder.name
+";
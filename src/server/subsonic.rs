@@ -0,0 +1,953 @@
+use axum::Router;
+use axum::body::Body;
+use axum::extract::{Path, Request, State};
+use axum::http::{Method, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use const_format::concatcp;
+use gel_derive::Queryable;
+use serde_json::{Map, Value, json};
+use std::fmt::Write;
+use std::sync::Arc;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+use super::ServerState;
+use super::auth::Credentials;
+use crate::music::errors::CriticalErrorKind;
+use crate::music::filter::DEFAULT_FILTERS;
+use crate::music::music_file::open_music_file;
+use crate::music::playlist::GEN_PLAYLIST;
+use crate::music::ratings::Rating;
+
+const API_VERSION: &str = "1.16.1";
+const XMLNS: &str = "http://subsonic.org/restapi";
+const MAX_BODY_SIZE: usize = 64 * 1024;
+const DEFAULT_RANDOM_SONGS: usize = 10;
+const MAX_RANDOM_SONGS: usize = 500;
+const DEFAULT_SEARCH_COUNT: usize = 20;
+const COVER_NAMES: &[(&str, &str)] = &[
+    ("cover.jpg", "image/jpeg"),
+    ("folder.jpg", "image/jpeg"),
+    ("cover.png", "image/png"),
+    ("folder.png", "image/png"),
+];
+
+const ARTIST_PREFIX: &str = "ar-";
+const ALBUM_PREFIX: &str = "al-";
+const SONG_PREFIX: &str = "tr-";
+const PLAYLIST_PREFIX: &str = "pl-";
+
+#[derive(Clone)]
+struct SubsonicState {
+    server: ServerState,
+    credentials: Arc<Credentials>,
+}
+
+#[derive(Debug)]
+enum SubsonicError {
+    Generic(String),
+    MissingParameter(&'static str),
+    WrongCredentials,
+    NotFound(String),
+}
+
+impl SubsonicError {
+    fn code(&self) -> u32 {
+        match self {
+            SubsonicError::Generic(_) => 0,
+            SubsonicError::MissingParameter(_) => 10,
+            SubsonicError::WrongCredentials => 40,
+            SubsonicError::NotFound(_) => 70,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            SubsonicError::Generic(message) => message.clone(),
+            SubsonicError::MissingParameter(name) => {
+                format!("Required parameter is missing: {name}")
+            }
+            SubsonicError::WrongCredentials => "Wrong username or password".to_string(),
+            SubsonicError::NotFound(id) => format!("Requested data was not found: {id}"),
+        }
+    }
+}
+
+impl From<CriticalErrorKind> for SubsonicError {
+    fn from(error: CriticalErrorKind) -> Self {
+        SubsonicError::Generic(error.to_string())
+    }
+}
+
+impl From<gel_tokio::Error> for SubsonicError {
+    fn from(error: gel_tokio::Error) -> Self {
+        SubsonicError::Generic(error.to_string())
+    }
+}
+
+/// Query string and form parameters, which may be repeated
+struct Params(Vec<(String, String)>);
+
+impl Params {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn required(&self, name: &'static str) -> Result<&str, SubsonicError> {
+        self.get(name).ok_or(SubsonicError::MissingParameter(name))
+    }
+
+    fn count(&self, name: &str, default: usize) -> usize {
+        self.get(name)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    }
+
+    fn json(&self) -> bool {
+        matches!(self.get("f"), Some("json" | "jsonp"))
+    }
+
+    /// JavaScript function JSONP responses are wrapped in, only made of identifier characters
+    fn callback(&self) -> Option<&str> {
+        if self.get("f") != Some("jsonp") {
+            return None;
+        }
+        self.get("callback").filter(|callback| {
+            !callback.is_empty()
+                && callback
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '$' | '.'))
+        })
+    }
+}
+
+enum Reply {
+    Body(Map<String, Value>),
+    Raw(Response),
+}
+
+#[derive(Queryable)]
+struct SongFolder {
+    name: String,
+    path: String,
+}
+
+#[derive(Queryable)]
+struct RatedMusic {
+    folders: Vec<SongFolder>,
+}
+
+#[derive(Queryable)]
+struct SongResult {
+    id: uuid::Uuid,
+    name: String,
+    artist_id: uuid::Uuid,
+    artist_name: String,
+    album_id: uuid::Uuid,
+    album_name: String,
+    genre_name: String,
+    length: i64,
+    size: i64,
+    track: i64,
+    rating: f64,
+    folders: Vec<SongFolder>,
+}
+
+impl SongResult {
+    fn child(&self) -> Value {
+        let path = self
+            .folders
+            .first()
+            .map_or("", |folder| folder.path.as_str());
+        let relative_path = self
+            .folders
+            .first()
+            .and_then(|folder| {
+                std::path::Path::new(&folder.path)
+                    .strip_prefix(&folder.name)
+                    .ok()
+            })
+            .map_or_else(|| path.to_string(), |p| p.display().to_string());
+        let suffix = std::path::Path::new(path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let mut child = json!({
+            "id": format!("{SONG_PREFIX}{}", self.id),
+            "parent": format!("{ALBUM_PREFIX}{}", self.album_id),
+            "isDir": false,
+            "title": self.name,
+            "album": self.album_name,
+            "artist": self.artist_name,
+            "genre": self.genre_name,
+            "track": self.track,
+            "size": self.size,
+            "duration": self.length,
+            "suffix": suffix,
+            "contentType": content_type(&suffix),
+            "path": relative_path,
+            "albumId": format!("{ALBUM_PREFIX}{}", self.album_id),
+            "artistId": format!("{ARTIST_PREFIX}{}", self.artist_id),
+            "coverArt": format!("{ALBUM_PREFIX}{}", self.album_id),
+            "type": "music",
+        });
+        let user_rating = user_rating(self.rating);
+        if user_rating > 0 {
+            child["userRating"] = json!(user_rating);
+        }
+        child
+    }
+}
+
+#[derive(Queryable)]
+struct ArtistResult {
+    id: uuid::Uuid,
+    name: String,
+    album_count: i64,
+}
+
+impl ArtistResult {
+    fn artist(&self) -> Value {
+        json!({
+            "id": format!("{ARTIST_PREFIX}{}", self.id),
+            "name": self.name,
+            "albumCount": self.album_count,
+        })
+    }
+}
+
+#[derive(Queryable)]
+struct AlbumResult {
+    id: uuid::Uuid,
+    name: String,
+    artist_id: uuid::Uuid,
+    artist_name: String,
+    song_count: i64,
+    duration: i64,
+}
+
+impl AlbumResult {
+    fn album(&self) -> Value {
+        let id = format!("{ALBUM_PREFIX}{}", self.id);
+        json!({
+            "id": id,
+            "parent": format!("{ARTIST_PREFIX}{}", self.artist_id),
+            "isDir": true,
+            "title": self.name,
+            "name": self.name,
+            "album": self.name,
+            "artist": self.artist_name,
+            "artistId": format!("{ARTIST_PREFIX}{}", self.artist_id),
+            "coverArt": id,
+            "songCount": self.song_count,
+            "duration": self.duration,
+        })
+    }
+}
+
+/// Subsonic REST API, methods being reachable with or without the .view suffix
+pub fn router(state: ServerState, credentials: Credentials) -> Router {
+    Router::new()
+        .route("/rest/{method}", get(rest).post(rest))
+        .with_state(SubsonicState {
+            server: state,
+            credentials: Arc::new(credentials),
+        })
+}
+
+async fn rest(
+    State(state): State<SubsonicState>,
+    Path(method): Path<String>,
+    request: Request,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let mut params: Vec<(String, String)> = parts
+        .uri
+        .query()
+        .map(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .into_owned()
+                .collect()
+        })
+        .unwrap_or_default();
+    if parts.method == Method::POST
+        && let Ok(body) = axum::body::to_bytes(body, MAX_BODY_SIZE).await
+    {
+        params.extend(url::form_urlencoded::parse(&body).into_owned());
+    }
+    let params = Params(params);
+    let request = Request::from_parts(parts, Body::empty());
+
+    let method = method.trim_end_matches(".view");
+    let reply = match state.credentials.check_subsonic(&params.0) {
+        Ok(true) if params.get("f") == Some("jsonp") && params.callback().is_none() => {
+            Err(SubsonicError::MissingParameter("callback"))
+        }
+        Ok(true) => Box::pin(call(&state.server, method, &params, request)).await,
+        Ok(false) => Err(SubsonicError::WrongCredentials),
+        Err(name) => Err(SubsonicError::MissingParameter(name)),
+    };
+    match reply {
+        Ok(Reply::Raw(response)) => response,
+        Ok(Reply::Body(body)) => respond(&params, "ok", body),
+        Err(e) => {
+            let mut body = Map::new();
+            body.insert(
+                "error".to_string(),
+                json!({"code": e.code(), "message": e.message()}),
+            );
+            respond(&params, "failed", body)
+        }
+    }
+}
+
+async fn call(
+    state: &ServerState,
+    method: &str,
+    params: &Params,
+    request: Request,
+) -> Result<Reply, SubsonicError> {
+    let body = match method {
+        "ping" => Map::new(),
+        "getLicense" => object("license", json!({"valid": true})),
+        "getMusicFolders" => music_folders(state),
+        "getArtists" => object("artists", Box::pin(artists(state)).await?),
+        "getIndexes" => object("indexes", Box::pin(artists(state)).await?),
+        "getArtist" => Box::pin(artist(state, params)).await?,
+        "getAlbum" => Box::pin(album(state, params)).await?,
+        "getMusicDirectory" => Box::pin(music_directory(state, params)).await?,
+        "search3" => Box::pin(search(state, params)).await?,
+        "getRandomSongs" => Box::pin(random_songs(state, params)).await?,
+        "getPlaylists" => Box::pin(playlists(state)).await?,
+        "getPlaylist" => Box::pin(playlist(state, params)).await?,
+        "setRating" => Box::pin(set_rating(state, params)).await?,
+        "stream" => return Box::pin(stream(state, params, request)).await,
+        "getCoverArt" => return Box::pin(cover_art(state, params)).await,
+        _ => {
+            return Err(SubsonicError::Generic(format!(
+                "Unsupported method: {method}"
+            )));
+        }
+    };
+    Ok(Reply::Body(body))
+}
+
+fn object(key: &str, value: Value) -> Map<String, Value> {
+    let mut body = Map::new();
+    body.insert(key.to_string(), value);
+    body
+}
+
+fn music_folders(state: &ServerState) -> Map<String, Value> {
+    let folders = state
+        .folders()
+        .iter()
+        .enumerate()
+        .map(|(id, folder)| json!({"id": id, "name": folder.display().to_string()}))
+        .collect::<Vec<_>>();
+    object("musicFolders", json!({"musicFolder": folders}))
+}
+
+/// Artists indexed by their first letter
+async fn artists(state: &ServerState) -> Result<Value, SubsonicError> {
+    let artists: Vec<ArtistResult> = Box::pin(state.gel.query(ARTISTS_QUERY, &())).await?;
+    let mut indexes: Vec<(String, Vec<Value>)> = Vec::new();
+    for artist in &artists {
+        let letter = match artist.name.chars().next() {
+            Some(c) if c.is_alphabetic() => c.to_uppercase().to_string(),
+            _ => "#".to_string(),
+        };
+        match indexes.iter_mut().find(|(name, _)| *name == letter) {
+            Some((_, index)) => index.push(artist.artist()),
+            None => indexes.push((letter, vec![artist.artist()])),
+        }
+    }
+    let indexes = indexes
+        .into_iter()
+        .map(|(name, artists)| json!({"name": name, "artist": artists}))
+        .collect::<Vec<_>>();
+    Ok(json!({"ignoredArticles": "", "lastModified": 0, "index": indexes}))
+}
+
+async fn artist(state: &ServerState, params: &Params) -> Result<Map<String, Value>, SubsonicError> {
+    let id = params.required("id")?;
+    let (artist, albums) = Box::pin(artist_albums(state, id)).await?;
+    let mut artist = artist.artist();
+    artist["album"] = Value::Array(albums.iter().map(AlbumResult::album).collect());
+    Ok(object("artist", artist))
+}
+
+async fn artist_albums(
+    state: &ServerState,
+    id: &str,
+) -> Result<(ArtistResult, Vec<AlbumResult>), SubsonicError> {
+    let artist_id = parse_id(id, ARTIST_PREFIX)?;
+    let Some(artist): Option<ArtistResult> =
+        Box::pin(state.gel.query_single(ARTIST_QUERY, &(artist_id,))).await?
+    else {
+        return Err(SubsonicError::NotFound(id.to_string()));
+    };
+    let albums: Vec<AlbumResult> =
+        Box::pin(state.gel.query(ARTIST_ALBUMS_QUERY, &(artist_id,))).await?;
+    Ok((artist, albums))
+}
+
+async fn album(state: &ServerState, params: &Params) -> Result<Map<String, Value>, SubsonicError> {
+    let id = params.required("id")?;
+    let (album, songs) = Box::pin(album_songs(state, id)).await?;
+    let mut album = album.album();
+    album["song"] = Value::Array(songs.iter().map(SongResult::child).collect());
+    Ok(object("album", album))
+}
+
+async fn album_songs(
+    state: &ServerState,
+    id: &str,
+) -> Result<(AlbumResult, Vec<SongResult>), SubsonicError> {
+    let album_id = parse_id(id, ALBUM_PREFIX)?;
+    let Some(album): Option<AlbumResult> =
+        Box::pin(state.gel.query_single(ALBUM_QUERY, &(album_id,))).await?
+    else {
+        return Err(SubsonicError::NotFound(id.to_string()));
+    };
+    let songs: Vec<SongResult> = Box::pin(state.gel.query(ALBUM_SONGS_QUERY, &(album_id,))).await?;
+    Ok((album, songs))
+}
+
+/// Folder-like browsing, artists containing albums containing songs
+async fn music_directory(
+    state: &ServerState,
+    params: &Params,
+) -> Result<Map<String, Value>, SubsonicError> {
+    let id = params.required("id")?;
+    let directory = if id.starts_with(ARTIST_PREFIX) {
+        let (artist, albums) = Box::pin(artist_albums(state, id)).await?;
+        json!({
+            "id": id,
+            "name": artist.name,
+            "child": albums.iter().map(AlbumResult::album).collect::<Vec<_>>(),
+        })
+    } else {
+        let (album, songs) = Box::pin(album_songs(state, id)).await?;
+        json!({
+            "id": id,
+            "parent": format!("{ARTIST_PREFIX}{}", album.artist_id),
+            "name": album.name,
+            "child": songs.iter().map(SongResult::child).collect::<Vec<_>>(),
+        })
+    };
+    Ok(object("directory", directory))
+}
+
+async fn search(state: &ServerState, params: &Params) -> Result<Map<String, Value>, SubsonicError> {
+    // Some clients list the whole library with an empty quoted query
+    let query = params.get("query").unwrap_or_default().trim_matches('"');
+    let page = |kind: &str| -> (i64, i64) {
+        let count = params.count(&format!("{kind}Count"), DEFAULT_SEARCH_COUNT);
+        let offset = params.count(&format!("{kind}Offset"), 0);
+        (
+            i64::try_from(offset).unwrap_or(i64::MAX),
+            i64::try_from(count).unwrap_or(i64::MAX),
+        )
+    };
+    let (offset, limit) = page("artist");
+    let artists: Vec<ArtistResult> = Box::pin(
+        state
+            .gel
+            .query(SEARCH_ARTISTS_QUERY, &(query, offset, limit)),
+    )
+    .await?;
+    let (offset, limit) = page("album");
+    let albums: Vec<AlbumResult> = Box::pin(
+        state
+            .gel
+            .query(SEARCH_ALBUMS_QUERY, &(query, offset, limit)),
+    )
+    .await?;
+    let (offset, limit) = page("song");
+    let songs: Vec<SongResult> =
+        Box::pin(state.gel.query(SEARCH_SONGS_QUERY, &(query, offset, limit))).await?;
+    Ok(object(
+        "searchResult3",
+        json!({
+            "artist": artists.iter().map(ArtistResult::artist).collect::<Vec<_>>(),
+            "album": albums.iter().map(AlbumResult::album).collect::<Vec<_>>(),
+            "song": songs.iter().map(SongResult::child).collect::<Vec<_>>(),
+        }),
+    ))
+}
+
+async fn random_songs(
+    state: &ServerState,
+    params: &Params,
+) -> Result<Map<String, Value>, SubsonicError> {
+    let size = params
+        .count("size", DEFAULT_RANDOM_SONGS)
+        .min(MAX_RANDOM_SONGS);
+    let genre = params.get("genre").unwrap_or_default();
+    let songs: Vec<SongResult> = Box::pin(state.gel.query(
+        RANDOM_SONGS_QUERY,
+        &(i64::try_from(size).unwrap_or_default(), genre),
+    ))
+    .await?;
+    Ok(object(
+        "randomSongs",
+        json!({"song": songs.iter().map(SongResult::child).collect::<Vec<_>>()}),
+    ))
+}
+
+/// Default filters exposed as read-only playlists
+async fn playlists(state: &ServerState) -> Result<Map<String, Value>, SubsonicError> {
+    let mut names = DEFAULT_FILTERS.keys().collect::<Vec<_>>();
+    names.sort();
+    let mut playlists = Vec::new();
+    for name in names {
+        let songs = Box::pin(preset_songs(state, name)).await?;
+        playlists.push(playlist_entry(name, &songs));
+    }
+    Ok(object("playlists", json!({"playlist": playlists})))
+}
+
+async fn playlist(
+    state: &ServerState,
+    params: &Params,
+) -> Result<Map<String, Value>, SubsonicError> {
+    let id = params.required("id")?;
+    let Some(name) = id.strip_prefix(PLAYLIST_PREFIX) else {
+        return Err(SubsonicError::NotFound(id.to_string()));
+    };
+    let songs = Box::pin(preset_songs(state, name)).await?;
+    let mut playlist = playlist_entry(name, &songs);
+    playlist["entry"] = Value::Array(songs.iter().map(SongResult::child).collect());
+    Ok(object("playlist", playlist))
+}
+
+async fn preset_songs(state: &ServerState, name: &str) -> Result<Vec<SongResult>, SubsonicError> {
+    let Some(filter) = DEFAULT_FILTERS.get(name) else {
+        return Err(SubsonicError::NotFound(format!("{PLAYLIST_PREFIX}{name}")));
+    };
+    let music_filter = serde_json::to_string(filter).map_err(CriticalErrorKind::from)?;
+    Ok(Box::pin(state.gel.query(PRESET_SONGS_QUERY, &(music_filter,))).await?)
+}
+
+fn playlist_entry(name: &str, songs: &[SongResult]) -> Value {
+    json!({
+        "id": format!("{PLAYLIST_PREFIX}{name}"),
+        "name": name,
+        "public": true,
+        "readonly": true,
+        "songCount": songs.len(),
+        "duration": songs.iter().map(|song| song.length).sum::<i64>(),
+    })
+}
+
+/// Store the rating, then write it to tags of files available on this host
+async fn set_rating(
+    state: &ServerState,
+    params: &Params,
+) -> Result<Map<String, Value>, SubsonicError> {
+    let id = params.required("id")?;
+    let music_id = parse_id(id, SONG_PREFIX)?;
+    let rating = params
+        .required("rating")?
+        .parse::<u8>()
+        .ok()
+        .filter(|rating| *rating <= 5)
+        .ok_or_else(|| SubsonicError::Generic("Rating should be between 0 and 5".to_string()))?;
+    let rating = Rating::try_from(f64::from(rating))?;
+    let Some(music): Option<RatedMusic> =
+        Box::pin(state.gel.query_single(RATED_MUSIC_QUERY, &(music_id,))).await?
+    else {
+        return Err(SubsonicError::NotFound(id.to_string()));
+    };
+
+    // Tags are written first, the rating is only stored once every local file has it
+    for folder in music.folders {
+        if state.resolve(&folder.path).is_err() {
+            continue;
+        }
+        open_music_file(&folder.name, &folder.path)
+            .and_then(|mut music_file| {
+                music_file.set_rating(rating);
+                music_file.save()
+            })
+            .map_err(|e| {
+                SubsonicError::Generic(format!(
+                    "Rating not saved, tags of {} not written: {e}",
+                    folder.path
+                ))
+            })?;
+    }
+    let _: Option<uuid::Uuid> = Box::pin(
+        state
+            .gel
+            .query_single(SET_RATING_QUERY, &(music_id, f64::from(rating))),
+    )
+    .await?;
+    Ok(Map::new())
+}
+
+async fn local_song(state: &ServerState, id: &str) -> Result<std::path::PathBuf, SubsonicError> {
+    let folders: Vec<SongFolder> = if id.starts_with(ALBUM_PREFIX) {
+        let album_id = parse_id(id, ALBUM_PREFIX)?;
+        Box::pin(state.gel.query(ALBUM_PATHS_QUERY, &(album_id,))).await?
+    } else {
+        let music_id = parse_id(id, SONG_PREFIX)?;
+        Box::pin(state.gel.query(SONG_PATHS_QUERY, &(music_id,))).await?
+    };
+    folders
+        .iter()
+        .find_map(|folder| state.resolve(&folder.path).ok())
+        .ok_or_else(|| SubsonicError::NotFound(id.to_string()))
+}
+
+async fn stream(
+    state: &ServerState,
+    params: &Params,
+    request: Request,
+) -> Result<Reply, SubsonicError> {
+    let path = Box::pin(local_song(state, params.required("id")?)).await?;
+    Ok(Reply::Raw(
+        ServeFile::new(path).oneshot(request).await.into_response(),
+    ))
+}
+
+/// Picture embedded in the music file, or an image beside it
+async fn cover_art(state: &ServerState, params: &Params) -> Result<Reply, SubsonicError> {
+    let id = params.required("id")?;
+    let path = Box::pin(local_song(state, id)).await?;
+    let Some((mime_type, data)) = embedded_picture(&path).or_else(|| cover_file(&path)) else {
+        return Err(SubsonicError::NotFound(id.to_string()));
+    };
+    Ok(Reply::Raw(
+        ([(header::CONTENT_TYPE, mime_type)], data).into_response(),
+    ))
+}
+
+fn embedded_picture(path: &std::path::Path) -> Option<(String, Vec<u8>)> {
+    match path.extension()?.to_str()? {
+        "flac" => {
+            let tag = metaflac::Tag::read_from_path(path).ok()?;
+            let picture = tag.pictures().next()?;
+            Some((picture.mime_type.clone(), picture.data.clone()))
+        }
+        "mp3" => {
+            let tag = id3::Tag::read_from_path(path).ok()?;
+            let picture = tag.pictures().next()?;
+            Some((picture.mime_type.clone(), picture.data.clone()))
+        }
+        _ => None,
+    }
+}
+
+fn cover_file(path: &std::path::Path) -> Option<(String, Vec<u8>)> {
+    let dir = path.parent()?;
+    COVER_NAMES.iter().find_map(|(name, mime_type)| {
+        let data = std::fs::read(dir.join(name)).ok()?;
+        Some(((*mime_type).to_string(), data))
+    })
+}
+
+fn parse_id(id: &str, prefix: &str) -> Result<uuid::Uuid, SubsonicError> {
+    id.strip_prefix(prefix)
+        .and_then(|uuid| uuid::Uuid::parse_str(uuid).ok())
+        .ok_or_else(|| SubsonicError::NotFound(id.to_string()))
+}
+
+/// Subsonic ratings are whole stars
+#[allow(clippy::cast_possible_truncation)]
+fn user_rating(rating: f64) -> i64 {
+    rating.round() as i64
+}
+
+fn content_type(suffix: &str) -> &'static str {
+    match suffix {
+        "flac" => "audio/flac",
+        "mp3" => "audio/mpeg",
+        "ogg" | "opus" => "audio/ogg",
+        _ => "application/octet-stream",
+    }
+}
+
+fn respond(params: &Params, status: &str, body: Map<String, Value>) -> Response {
+    let mut response = Map::new();
+    response.insert("status".to_string(), json!(status));
+    response.insert("version".to_string(), json!(API_VERSION));
+    response.extend(body);
+    if params.json() {
+        let response = json!({"subsonic-response": response});
+        if let Some(callback) = params.callback() {
+            return (
+                [(header::CONTENT_TYPE, "application/javascript")],
+                format!("{callback}({response});"),
+            )
+                .into_response();
+        }
+        return (
+            [(header::CONTENT_TYPE, "application/json")],
+            response.to_string(),
+        )
+            .into_response();
+    }
+    response.insert("xmlns".to_string(), json!(XMLNS));
+    let mut xml = r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string();
+    json_to_xml(&mut xml, "subsonic-response", &Value::Object(response));
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/xml; charset=utf-8")],
+        xml,
+    )
+        .into_response()
+}
+
+/// Scalars become attributes, objects and arrays become child elements
+fn json_to_xml(xml: &mut String, name: &str, value: &Value) {
+    match value {
+        Value::Array(values) => {
+            for value in values {
+                json_to_xml(xml, name, value);
+            }
+        }
+        Value::Object(fields) => {
+            xml.push('<');
+            xml.push_str(name);
+            let mut children = Vec::new();
+            for (key, value) in fields {
+                match value {
+                    Value::Array(_) | Value::Object(_) => children.push((key, value)),
+                    Value::Null => {}
+                    scalar => {
+                        let _ = write!(xml, r#" {key}="{}""#, escape_xml(&scalar_text(scalar)));
+                    }
+                }
+            }
+            if children.is_empty() {
+                xml.push_str("/>");
+                return;
+            }
+            xml.push('>');
+            for (key, value) in children {
+                json_to_xml(xml, key, value);
+            }
+            let _ = write!(xml, "</{name}>");
+        }
+        Value::Null => {}
+        scalar => {
+            let _ = write!(xml, "<{name}>{}</{name}>", escape_xml(&scalar_text(scalar)));
+        }
+    }
+}
+
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+const ARTIST_FIELDS: &str = r"
+    id,
+    name,
+    album_count := count(.albums)
+";
+
+const ALBUM_FIELDS: &str = r"
+    id,
+    name,
+    artist_id := .artist.id,
+    artist_name := .artist.name,
+    song_count := count(.musics),
+    duration := sum(<int64>.musics.length)
+";
+
+const SONG_FIELDS: &str = r"
+    id,
+    name,
+    artist_id := .artist.id,
+    artist_name := .artist.name,
+    album_id := .album.id,
+    album_name := .album.name,
+    genre_name := .genre.name,
+    length,
+    size,
+    track,
+    rating,
+    folders: {
+        name,
+        path := @path
+    }
+";
+
+const PATHS_FIELDS: &str = r"
+    name,
+    path := @path
+";
+
+const ARTISTS_QUERY: &str = concatcp!("select Artist {", ARTIST_FIELDS, "} order by .name");
+
+const ARTIST_QUERY: &str = concatcp!("select Artist {", ARTIST_FIELDS, "} filter .id = <uuid>$0");
+
+const ARTIST_ALBUMS_QUERY: &str = concatcp!(
+    "select Album {",
+    ALBUM_FIELDS,
+    "} filter .artist.id = <uuid>$0 order by .name"
+);
+
+const ALBUM_QUERY: &str = concatcp!("select Album {", ALBUM_FIELDS, "} filter .id = <uuid>$0");
+
+const ALBUM_SONGS_QUERY: &str = concatcp!(
+    "select Music {",
+    SONG_FIELDS,
+    "} filter .album.id = <uuid>$0 order by .track then .name"
+);
+
+const SEARCH_ARTISTS_QUERY: &str = concatcp!(
+    "select Artist {",
+    ARTIST_FIELDS,
+    "} filter .name ilike '%' ++ <str>$0 ++ '%' order by .name offset <int64>$1 limit <int64>$2"
+);
+
+const SEARCH_ALBUMS_QUERY: &str = concatcp!(
+    "select Album {",
+    ALBUM_FIELDS,
+    "} filter .name ilike '%' ++ <str>$0 ++ '%' order by .name offset <int64>$1 limit <int64>$2"
+);
+
+const SEARCH_SONGS_QUERY: &str = concatcp!(
+    "select Music {",
+    SONG_FIELDS,
+    "} filter .name ilike '%' ++ <str>$0 ++ '%' order by .name offset <int64>$1 limit <int64>$2"
+);
+
+const RANDOM_SONGS_QUERY: &str = concatcp!(
+    "select Music {",
+    SONG_FIELDS,
+    "} filter <str>$1 = '' or .genre.name = <str>$1 order by random() limit <int64>$0"
+);
+
+const PRESET_SONGS_QUERY: &str = concatcp!(GEN_PLAYLIST, " {", SONG_FIELDS, "}");
+
+const RATED_MUSIC_QUERY: &str = concatcp!(
+    "select Music {
+    folders: {",
+    PATHS_FIELDS,
+    "}
+} filter .id = <uuid>$0"
+);
+
+const SET_RATING_QUERY: &str =
+    "select (update Music filter .id = <uuid>$0 set { rating := <Rating>$1 }).id";
+
+const SONG_PATHS_QUERY: &str = concatcp!(
+    "select (select Music filter .id = <uuid>$0).folders {",
+    PATHS_FIELDS,
+    "}"
+);
+
+const ALBUM_PATHS_QUERY: &str = concatcp!(
+    "select (select Music filter .album.id = <uuid>$0 order by .track).folders {",
+    PATHS_FIELDS,
+    "}"
+);
+
+#[tokio::test]
+async fn subsonic_tests() {
+    let folder = tempfile::tempdir().unwrap();
+    let gel_config = gel_tokio::Builder::new()
+        .dsn("gel://user@localhost:5656/main")
+        .build()
+        .unwrap();
+    let state = ServerState::with_folders(
+        gel_tokio::Client::new(&gel_config),
+        vec![folder.path().canonicalize().unwrap()],
+    );
+    let router = router(state, Credentials::new("admin", "sesame"));
+    let call = |uri: &str| {
+        router
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+    let body = |response: Response| async {
+        let body = axum::body::to_bytes(response.into_body(), MAX_BODY_SIZE)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    };
+
+    // Recorded from DSub, token being md5("sesame" + "c19b2d")
+    let response = call(
+        "/rest/ping.view?u=admin&t=26719a1196d2a940705a59634eb18eab&s=c19b2d&v=1.2.0&c=DSub&f=json",
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    let response: Value = serde_json::from_str(&body(response).await).unwrap();
+    assert_eq!(response["subsonic-response"]["status"], "ok");
+    assert_eq!(response["subsonic-response"]["version"], API_VERSION);
+
+    let response = call("/rest/ping?u=admin&p=enc:736573616d65&v=1.16.1&c=Symfonium")
+        .await
+        .unwrap();
+    assert_eq!(
+        body(response).await,
+        r#"<?xml version="1.0" encoding="UTF-8"?><subsonic-response status="ok" version="1.16.1" xmlns="http://subsonic.org/restapi"/>"#
+    );
+
+    let response = call("/rest/ping?u=admin&p=sesame&f=jsonp&callback=jQuery_1.cb")
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/javascript"
+    );
+    assert_eq!(
+        body(response).await,
+        r#"jQuery_1.cb({"subsonic-response":{"status":"ok","version":"1.16.1"}});"#
+    );
+    let response = call("/rest/ping?u=admin&p=sesame&f=jsonp&callback=alert(1)")
+        .await
+        .unwrap();
+    let response: Value = serde_json::from_str(&body(response).await).unwrap();
+    assert_eq!(response["subsonic-response"]["error"]["code"], 10);
+
+    let response = call("/rest/getMusicFolders?u=admin&p=wrong&v=1.16.1&c=test")
+        .await
+        .unwrap();
+    assert_eq!(
+        body(response).await,
+        r#"<?xml version="1.0" encoding="UTF-8"?><subsonic-response status="failed" version="1.16.1" xmlns="http://subsonic.org/restapi"><error code="40" message="Wrong username or password"/></subsonic-response>"#
+    );
+
+    let response = call("/rest/getMusicFolders?u=admin&p=sesame&f=json")
+        .await
+        .unwrap();
+    let response: Value = serde_json::from_str(&body(response).await).unwrap();
+    assert_eq!(
+        response["subsonic-response"]["musicFolders"]["musicFolder"][0]["name"],
+        folder.path().canonicalize().unwrap().display().to_string()
+    );
+
+    let response = call("/rest/getAlbum?u=admin&p=sesame&f=json")
+        .await
+        .unwrap();
+    let response: Value = serde_json::from_str(&body(response).await).unwrap();
+    assert_eq!(response["subsonic-response"]["error"]["code"], 10);
+
+    let response = call("/rest/getAlbum?u=admin&p=sesame&f=json&id=ar-1")
+        .await
+        .unwrap();
+    let response: Value = serde_json::from_str(&body(response).await).unwrap();
+    assert_eq!(response["subsonic-response"]["error"]["code"], 70);
+}
@@ -0,0 +1,102 @@
+use crate::music::music_result::{FolderResult, MusicResult};
+
+/// Music of Artist in Album, lasting a second, stored at a path of a folder
+#[must_use]
+pub fn music(name: &str, folder: &str, path: &str) -> MusicResult {
+    MusicResult {
+        name: name.to_string(),
+        artist_name: "Artist".to_string(),
+        album_name: "Album".to_string(),
+        genre_name: "Rock".to_string(),
+        length: 1,
+        human_duration: String::new(),
+        size: 1,
+        human_size: String::new(),
+        track: 1,
+        rating: 0.0,
+        keywords_names: Vec::new(),
+        folders: vec![FolderResult {
+            name: folder.to_string(),
+            username: "user".to_string(),
+            ipv4: "127.0.0.1".to_string(),
+            path: path.to_string(),
+        }],
+    }
+}
+
+/// 16 bits PCM WAV file of interleaved samples
+///
+/// # Panics
+///
+/// When samples do not fit in a WAV file
+#[must_use]
+pub fn wav(channels: u16, rate: u32, samples: &[i16]) -> Vec<u8> {
+    let size = u32::try_from(samples.len() * 2).unwrap();
+    let block_align = channels * 2;
+    let mut data = Vec::new();
+    data.extend_from_slice(b"RIFF");
+    data.extend_from_slice(&(36 + size).to_le_bytes());
+    data.extend_from_slice(b"WAVEfmt ");
+    data.extend_from_slice(&16_u32.to_le_bytes());
+    data.extend_from_slice(&1_u16.to_le_bytes());
+    data.extend_from_slice(&channels.to_le_bytes());
+    data.extend_from_slice(&rate.to_le_bytes());
+    data.extend_from_slice(&(rate * u32::from(block_align)).to_le_bytes());
+    data.extend_from_slice(&block_align.to_le_bytes());
+    data.extend_from_slice(&16_u16.to_le_bytes());
+    data.extend_from_slice(b"data");
+    data.extend_from_slice(&size.to_le_bytes());
+    for sample in samples {
+        data.extend_from_slice(&sample.to_le_bytes());
+    }
+    data
+}
+
+/// Next number of a seeded 64 bits linear congruential generator, for reproducible test data
+pub fn lcg(state: &mut u64) -> u64 {
+    *state = state
+        .wrapping_mul(6_364_136_223_846_793_005)
+        .wrapping_add(1_442_695_040_888_963_407);
+    *state
+}
+
+/// MPEG-1 Layer III mono file of 128 kbps 44.1 KHz frames, their main data being seeded noise
+#[must_use]
+pub fn mp3(frames: usize, seed: u64) -> Vec<u8> {
+    const FRAME_SIZE: usize = 417;
+    // main_data_begin, private bits and scfsi, then granules reading 1500 bits of main data
+    // with Huffman table 15, 400 values being big ones
+    let granule = [
+        (1500, 12),
+        (200, 9),
+        (150, 8),
+        (0, 4),
+        (0, 1),
+        (15, 5),
+        (15, 5),
+        (15, 5),
+        (7, 4),
+        (7, 3),
+        (0, 3),
+    ];
+    let mut side_info = vec![0_u8; 17];
+    let mut position = 18;
+    for (value, size) in granule.iter().chain(granule.iter()) {
+        for bit in (0..*size).rev() {
+            if value >> bit & 1 == 1 {
+                side_info[position / 8] |= 0x80 >> (position % 8);
+            }
+            position += 1;
+        }
+    }
+    let mut state = seed;
+    let mut data = Vec::with_capacity(frames * FRAME_SIZE);
+    for _ in 0..frames {
+        data.extend_from_slice(&[0xff, 0xfb, 0x90, 0xc0]);
+        data.extend_from_slice(&side_info);
+        while data.len() % FRAME_SIZE != 0 {
+            data.push(lcg(&mut state).to_le_bytes()[7]);
+        }
+    }
+    data
+}
@@ -0,0 +1,399 @@
+pub mod ui;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::music::music_result::MusicResult;
+use crate::music::ratings::Rating;
+
+const RATING_STEP: f64 = 0.5;
+const MAX_RATING: f64 = 5.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pane {
+    Artists,
+    Albums,
+    Tracks,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Browse,
+    Filter,
+    Keywords,
+}
+
+/// What the command should do after a key press
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    None,
+    Quit,
+    Write,
+    ApplyFilter(String),
+    Rate(MusicResult, Rating),
+    SetKeywords(MusicResult, Vec<String>),
+}
+
+/// Library browser state, artists containing albums containing tracks
+pub struct App {
+    musics: Vec<MusicResult>,
+    artists: Vec<String>,
+    albums: Vec<String>,
+    tracks: Vec<usize>,
+    selected: [usize; 3],
+    pane: Pane,
+    mode: Mode,
+    input: String,
+    filter: String,
+    status: String,
+    playlist: Vec<MusicResult>,
+}
+
+impl App {
+    #[must_use]
+    pub fn new(musics: Vec<MusicResult>) -> Self {
+        let mut app = Self {
+            musics: Vec::new(),
+            artists: Vec::new(),
+            albums: Vec::new(),
+            tracks: Vec::new(),
+            selected: [0; 3],
+            pane: Pane::Artists,
+            mode: Mode::Browse,
+            input: String::new(),
+            filter: String::new(),
+            status: String::new(),
+            playlist: Vec::new(),
+        };
+        app.set_musics(musics);
+        app
+    }
+
+    /// Replace browsed musics, after a filter has been applied
+    pub fn set_musics(&mut self, musics: Vec<MusicResult>) {
+        self.musics = musics;
+        self.artists = self
+            .musics
+            .iter()
+            .map(|music| music.artist_name.clone())
+            .collect();
+        self.artists.sort();
+        self.artists.dedup();
+        self.selected = [0; 3];
+        self.refresh();
+    }
+
+    fn refresh(&mut self) {
+        self.selected[0] = self.selected[0].min(self.artists.len().saturating_sub(1));
+        let artist = self.artists.get(self.selected[0]).cloned();
+        self.albums = self
+            .musics
+            .iter()
+            .filter(|music| Some(&music.artist_name) == artist.as_ref())
+            .map(|music| music.album_name.clone())
+            .collect();
+        self.albums.sort();
+        self.albums.dedup();
+
+        self.selected[1] = self.selected[1].min(self.albums.len().saturating_sub(1));
+        let album = self.albums.get(self.selected[1]).cloned();
+        self.tracks = (0..self.musics.len())
+            .filter(|i| {
+                Some(&self.musics[*i].artist_name) == artist.as_ref()
+                    && Some(&self.musics[*i].album_name) == album.as_ref()
+            })
+            .collect();
+        self.tracks.sort_by(|a, b| {
+            (self.musics[*a].track, &self.musics[*a].name)
+                .cmp(&(self.musics[*b].track, &self.musics[*b].name))
+        });
+        self.selected[2] = self.selected[2].min(self.tracks.len().saturating_sub(1));
+    }
+
+    #[must_use]
+    pub fn artists(&self) -> &[String] {
+        &self.artists
+    }
+
+    #[must_use]
+    pub fn albums(&self) -> &[String] {
+        &self.albums
+    }
+
+    pub fn tracks(&self) -> impl Iterator<Item = &MusicResult> {
+        self.tracks.iter().map(|i| &self.musics[*i])
+    }
+
+    #[must_use]
+    pub fn selected(&self, pane: Pane) -> usize {
+        self.selected[pane as usize]
+    }
+
+    #[must_use]
+    pub fn pane(&self) -> Pane {
+        self.pane
+    }
+
+    #[must_use]
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    #[must_use]
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    #[must_use]
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    #[must_use]
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    #[must_use]
+    pub fn playlist(&self) -> &[MusicResult] {
+        &self.playlist
+    }
+
+    #[must_use]
+    pub fn in_playlist(&self, music: &MusicResult) -> bool {
+        self.playlist.contains(music)
+    }
+
+    #[must_use]
+    pub fn selected_music(&self) -> Option<&MusicResult> {
+        self.tracks.get(self.selected[2]).map(|i| &self.musics[*i])
+    }
+
+    /// Musics of the selected artist, album or track, depending on the focused pane
+    #[must_use]
+    pub fn selected_musics(&self) -> Vec<MusicResult> {
+        match self.pane {
+            Pane::Artists => {
+                let artist = self.artists.get(self.selected[0]);
+                self.musics
+                    .iter()
+                    .filter(|music| Some(&music.artist_name) == artist)
+                    .cloned()
+                    .collect()
+            }
+            Pane::Albums => self.tracks().cloned().collect(),
+            Pane::Tracks => self.selected_music().cloned().into_iter().collect(),
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let len = match self.pane {
+            Pane::Artists => self.artists.len(),
+            Pane::Albums => self.albums.len(),
+            Pane::Tracks => self.tracks.len(),
+        };
+        let selected = &mut self.selected[self.pane as usize];
+        *selected = selected
+            .saturating_add_signed(delta)
+            .min(len.saturating_sub(1));
+        match self.pane {
+            Pane::Artists => self.selected[1..].fill(0),
+            Pane::Albums => self.selected[2] = 0,
+            Pane::Tracks => {}
+        }
+        self.refresh();
+    }
+
+    fn add_selected(&mut self) {
+        let mut added = 0;
+        for music in self.selected_musics() {
+            if !self.playlist.contains(&music) {
+                self.playlist.push(music);
+                added += 1;
+            }
+        }
+        self.status = format!("{added} added, {} in playlist", self.playlist.len());
+    }
+
+    fn remove_selected(&mut self) {
+        let selected = self.selected_musics();
+        let before = self.playlist.len();
+        self.playlist.retain(|music| !selected.contains(music));
+        self.status = format!(
+            "{} removed, {} in playlist",
+            before - self.playlist.len(),
+            self.playlist.len()
+        );
+    }
+
+    /// Change the selected track, the browsed and playlist copies
+    pub fn update_music<F>(&mut self, music: &MusicResult, update: F)
+    where
+        F: Fn(&mut MusicResult),
+    {
+        self.musics
+            .iter_mut()
+            .chain(self.playlist.iter_mut())
+            .filter(|m| *m == music)
+            .for_each(update);
+    }
+
+    fn rate(&self, rating: f64) -> Action {
+        let Some(music) = self.selected_music() else {
+            return Action::None;
+        };
+        match Rating::try_from(rating.clamp(0.0, MAX_RATING)) {
+            Ok(rating) => Action::Rate(music.clone(), rating),
+            Err(_) => Action::None,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Action::Quit;
+        }
+        match self.mode {
+            Mode::Browse => self.browse(key.code),
+            Mode::Filter | Mode::Keywords => self.edit(key.code),
+        }
+    }
+
+    fn browse(&mut self, code: KeyCode) -> Action {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('w') => return Action::Write,
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Left | KeyCode::Char('h') | KeyCode::BackTab => {
+                self.pane = match self.pane {
+                    Pane::Artists | Pane::Albums => Pane::Artists,
+                    Pane::Tracks => Pane::Albums,
+                };
+            }
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Tab => {
+                self.pane = match self.pane {
+                    Pane::Artists => Pane::Albums,
+                    Pane::Albums | Pane::Tracks => Pane::Tracks,
+                };
+            }
+            KeyCode::Char('a') | KeyCode::Enter => self.add_selected(),
+            KeyCode::Char('d') => self.remove_selected(),
+            KeyCode::Char('/') => {
+                self.mode = Mode::Filter;
+                self.input = self.filter.clone();
+            }
+            KeyCode::Char('e') => {
+                if let Some(music) = self.selected_music() {
+                    self.input = music.keywords_names.join(" ");
+                    self.mode = Mode::Keywords;
+                }
+            }
+            KeyCode::Char(c @ '0'..='5') => {
+                return self.rate(f64::from(c.to_digit(10).unwrap_or_default()));
+            }
+            KeyCode::Char('+') => {
+                let rating = self.selected_music().map_or(0.0, |m| m.rating);
+                return self.rate(rating + RATING_STEP);
+            }
+            KeyCode::Char('-') => {
+                let rating = self.selected_music().map_or(0.0, |m| m.rating);
+                return self.rate(rating - RATING_STEP);
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn edit(&mut self, code: KeyCode) -> Action {
+        match code {
+            KeyCode::Esc => self.mode = Mode::Browse,
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Enter => {
+                let mode = std::mem::replace(&mut self.mode, Mode::Browse);
+                let input = std::mem::take(&mut self.input);
+                if mode == Mode::Filter {
+                    self.filter.clone_from(&input);
+                    return Action::ApplyFilter(input);
+                }
+                if let Some(music) = self.selected_music() {
+                    let keywords = input.split_whitespace().map(str::to_string).collect();
+                    return Action::SetKeywords(music.clone(), keywords);
+                }
+            }
+            _ => {}
+        }
+        Action::None
+    }
+}
+
+#[test]
+fn app_tests() {
+    let music = |artist: &str, album: &str, track: i64, name: &str| MusicResult {
+        artist_name: artist.to_string(),
+        album_name: album.to_string(),
+        track,
+        rating: 4.0,
+        keywords_names: vec!["live".to_string()],
+        folders: Vec::new(),
+        ..crate::test_helpers::music(name, "", "")
+    };
+    let key = |code: KeyCode| KeyEvent::new(code, KeyModifiers::NONE);
+    let mut app = App::new(vec![
+        music("B", "Second", 2, "b2"),
+        music("A", "First", 2, "a2"),
+        music("A", "First", 1, "a1"),
+        music("A", "Other", 1, "o1"),
+    ]);
+
+    assert_eq!(app.artists(), ["A", "B"]);
+    assert_eq!(app.albums(), ["First", "Other"]);
+    assert_eq!(
+        app.tracks().map(|m| m.name.as_str()).collect::<Vec<_>>(),
+        ["a1", "a2"]
+    );
+
+    // Whole artist, then a single track
+    assert_eq!(app.handle_key(key(KeyCode::Char('a'))), Action::None);
+    assert_eq!(app.playlist().len(), 3);
+    app.handle_key(key(KeyCode::Tab));
+    app.handle_key(key(KeyCode::Tab));
+    app.handle_key(key(KeyCode::Down));
+    assert_eq!(app.selected_music().unwrap().name, "a2");
+    app.handle_key(key(KeyCode::Char('d')));
+    assert_eq!(app.playlist().len(), 2);
+
+    let Action::Rate(rated, rating) = app.handle_key(key(KeyCode::Char('+'))) else {
+        panic!("rating expected");
+    };
+    assert_eq!(rated.name, "a2");
+    assert!((f64::from(rating) - 4.5).abs() < f64::EPSILON);
+    app.update_music(&rated, |m| m.rating = rating.into());
+    assert!((app.selected_music().unwrap().rating - 4.5).abs() < f64::EPSILON);
+
+    app.handle_key(key(KeyCode::Char('e')));
+    assert_eq!(app.mode(), Mode::Keywords);
+    assert_eq!(app.input(), "live");
+    app.handle_key(key(KeyCode::Char(' ')));
+    app.handle_key(key(KeyCode::Char('x')));
+    assert_eq!(
+        app.handle_key(key(KeyCode::Enter)),
+        Action::SetKeywords(rated, vec!["live".to_string(), "x".to_string()])
+    );
+
+    app.handle_key(key(KeyCode::Char('/')));
+    for c in "genre=Rock".chars() {
+        app.handle_key(key(KeyCode::Char(c)));
+    }
+    assert_eq!(
+        app.handle_key(key(KeyCode::Enter)),
+        Action::ApplyFilter("genre=Rock".to_string())
+    );
+    assert_eq!(app.filter(), "genre=Rock");
+    assert_eq!(app.handle_key(key(KeyCode::Char('w'))), Action::Write);
+}
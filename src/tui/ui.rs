@@ -0,0 +1,97 @@
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+
+use super::{App, Mode, Pane};
+use crate::music::links::LinkOptions;
+
+const HELP: &str =
+    "tab: pane, a/d: add/remove, 0-5 +/-: rate, e: keywords, /: filter, w: write, q: quit";
+
+pub fn draw(frame: &mut Frame, app: &App, link_options: &LinkOptions) {
+    let [main, bottom] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(3)]).areas(frame.area());
+    let [artists, albums, tracks, preview] = Layout::horizontal([
+        Constraint::Percentage(20),
+        Constraint::Percentage(20),
+        Constraint::Percentage(30),
+        Constraint::Percentage(30),
+    ])
+    .areas(main);
+
+    let artist_items = app
+        .artists()
+        .iter()
+        .map(|artist| ListItem::new(artist.as_str()));
+    draw_list(frame, app, Pane::Artists, "Artists", artist_items, artists);
+    let album_items = app
+        .albums()
+        .iter()
+        .map(|album| ListItem::new(album.as_str()));
+    draw_list(frame, app, Pane::Albums, "Albums", album_items, albums);
+    let track_items = app.tracks().map(|music| {
+        let marker = if app.in_playlist(music) { "+" } else { " " };
+        ListItem::new(format!("{marker} {:02} {}", music.track, music.name))
+    });
+    let title = format!("Tracks ({} in playlist)", app.playlist().len());
+    draw_list(frame, app, Pane::Tracks, &title, track_items, tracks);
+
+    let mut lines = Vec::new();
+    if let Some(music) = app.selected_music() {
+        lines.push(Line::from(music.name.clone()).bold());
+        lines.push(Line::from(format!("Artist: {}", music.artist_name)));
+        lines.push(Line::from(format!("Album: {}", music.album_name)));
+        lines.push(Line::from(format!("Genre: {}", music.genre_name)));
+        lines.push(Line::from(format!("Track: {}", music.track)));
+        lines.push(Line::from(format!("Rating: {}", music.rating)));
+        lines.push(Line::from(format!(
+            "Keywords: {}",
+            music.keywords_names.join(" ")
+        )));
+        lines.push(Line::from(format!("Duration: {}", music.human_duration)));
+        lines.push(Line::from(format!("Size: {}", music.human_size)));
+        lines.push(Line::from(""));
+        match music.all_links(link_options) {
+            Ok(links) => lines.extend(links.into_iter().map(Line::from)),
+            Err(e) => lines.push(Line::from(e.to_string())),
+        }
+    }
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title("Preview")),
+        preview,
+    );
+
+    let (title, text) = match app.mode() {
+        Mode::Filter => ("Filter, like min_rating=4,genre=Rock", app.input()),
+        Mode::Keywords => ("Keywords, separated by spaces", app.input()),
+        Mode::Browse if app.status().is_empty() => (HELP, app.filter()),
+        Mode::Browse => (HELP, app.status()),
+    };
+    frame.render_widget(
+        Paragraph::new(text).block(Block::bordered().title(title)),
+        bottom,
+    );
+}
+
+fn draw_list<'a>(
+    frame: &mut Frame,
+    app: &App,
+    pane: Pane,
+    title: &str,
+    items: impl Iterator<Item = ListItem<'a>>,
+    area: ratatui::layout::Rect,
+) {
+    let mut block = Block::bordered().title(title.to_string());
+    if app.pane() == pane {
+        block = block.border_style(Style::new().add_modifier(Modifier::BOLD));
+    }
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(Some(app.selected(pane)));
+    frame.render_stateful_widget(list, area, &mut state);
+}